@@ -0,0 +1,103 @@
+//! Compares the zero-copy fast path with the line-by-line loop.
+//!
+//! Run with `cargo bench`. The input size defaults to 2 GiB and can be
+//! changed with `CATR_BENCH_BYTES`, in bytes or with a K, M or G suffix
+//! (`CATR_BENCH_BYTES=8G cargo bench`); the file is created in the temp dir
+//! and removed afterwards. Each case runs `CATR_BENCH_RUNS` times (3 by
+//! default) and the fastest run is reported, so the page cache is warm for
+//! both paths alike.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PRG: &str = env!("CARGO_BIN_EXE_catr");
+const DEFAULT_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+const DEFAULT_RUNS: u32 = 3;
+const LINE: &[u8] = b"The quick brown fox jumps over the lazy dog. 0123456789\n";
+
+/// A byte count like `512M` or `4G`
+fn parse_size(s: &str) -> Option<u64> {
+    let (digits, shift) = match s.as_bytes().last()? {
+        b'K' | b'k' => (&s[..s.len() - 1], 10),
+        b'M' | b'm' => (&s[..s.len() - 1], 20),
+        b'G' | b'g' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn make_input(path: &Path, size: u64) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+    while written < size {
+        out.write_all(LINE)?;
+        written += LINE.len() as u64;
+    }
+    out.flush()
+}
+
+enum Sink {
+    DevNull,
+    Pipe,
+}
+
+fn time(args: &[&str], input: &Path, sink: &Sink) -> io::Result<Duration> {
+    let mut cmd = Command::new(PRG);
+    cmd.args(args).arg(input);
+    let start = Instant::now();
+    let status = match sink {
+        Sink::DevNull => cmd.stdout(File::create("/dev/null")?).status()?,
+        Sink::Pipe => {
+            let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+            let mut stdout = child.stdout.take().expect("piped stdout");
+            let reader = thread::spawn(move || -> io::Result<()> {
+                let mut buf = vec![0; 1 << 20];
+                while stdout.read(&mut buf)? > 0 {}
+                Ok(())
+            });
+            reader.join().expect("reader thread panicked")?;
+            child.wait()?
+        }
+    };
+    assert!(status.success(), "{PRG} {args:?} failed");
+    Ok(start.elapsed())
+}
+
+fn main() -> io::Result<()> {
+    let size = env::var("CATR_BENCH_BYTES")
+        .ok()
+        .and_then(|s| parse_size(&s))
+        .unwrap_or(DEFAULT_BYTES);
+    let runs = env::var("CATR_BENCH_RUNS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&runs| runs > 0)
+        .unwrap_or(DEFAULT_RUNS);
+    let input = env::temp_dir().join(format!("catr-bench-{}.txt", std::process::id()));
+    make_input(&input, size)?;
+
+    let mib = size as f64 / (1024.0 * 1024.0);
+    println!("input: {:.0} MiB, best of {} runs", mib, runs);
+    for (sink_name, sink) in [("/dev/null", Sink::DevNull), ("pipe", Sink::Pipe)] {
+        for (name, args) in [("fast path", &[][..]), ("line loop", &["--no-fast-path"][..])] {
+            let mut elapsed = time(args, &input, &sink)?;
+            for _ in 1..runs {
+                elapsed = elapsed.min(time(args, &input, &sink)?);
+            }
+            println!(
+                "{:>10} -> {:<9}  {:>8.2?}  {:>8.0} MiB/s",
+                name,
+                sink_name,
+                elapsed,
+                mib / elapsed.as_secs_f64()
+            );
+        }
+    }
+
+    fs::remove_file(&input)
+}
@@ -0,0 +1,103 @@
+//! Reading members straight out of tar (optionally compressed) and zip archives.
+
+use std::fs::File;
+use std::io::{self, BufRead, Seek};
+use std::path::Path;
+
+use thiserror::Error;
+use zip::ZipArchive;
+
+use crate::open::{open, Decoding, Input};
+use crate::MyResult;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("not a tar or zip archive")]
+    Unknown,
+    #[error("no such member in the archive")]
+    MissingMember,
+}
+
+/// Split `bundle.tar.gz:dir/file.txt` into the archive and the path inside it.
+/// None if `filename` exists as it is, or does not start with an existing file and a colon.
+pub fn split(filename: &str) -> Option<(&str, &str)> {
+    if Path::new(filename).exists() {
+        return None;
+    }
+    filename
+        .match_indices(':')
+        .map(|(i, _)| (&filename[..i], &filename[i + 1..]))
+        .find(|(archive, _)| Path::new(archive).is_file())
+}
+
+fn is_zip(head: &[u8]) -> bool {
+    head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06")
+}
+
+/// Whether `head` starts a tar archive: its first header carries the ustar magic
+fn is_tar(head: &[u8]) -> bool {
+    head.get(257..262) == Some(b"ustar")
+}
+
+/// The zip archive in `input`, which has to be spooled to a file first to be seekable
+fn zip(input: &mut Input) -> MyResult<ZipArchive<&mut File>> {
+    input.make_seekable()?;
+    let file = input
+        .file_mut()
+        .expect("a seekable input is a file")
+        .get_mut();
+    file.rewind()?;
+    Ok(ZipArchive::new(file)?)
+}
+
+/// Names of the members of the archive read from `input`, in archive order
+pub fn list(input: &mut Input) -> MyResult<Vec<String>> {
+    let head = input.fill_buf()?;
+    if is_zip(head) {
+        let zip = zip(input)?;
+        return Ok((0..zip.len())
+            .filter_map(|i| zip.name_for_index(i).map(String::from))
+            .collect());
+    } else if !is_tar(head) {
+        return Err(Box::new(ArchiveError::Unknown));
+    }
+
+    let mut names = Vec::new();
+    for entry in tar::Archive::new(input).entries()? {
+        names.push(entry?.path()?.to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+/// Copy the member `path` of `archive` into an anonymous temporary file, rewound to its start
+pub fn extract(archive: &str, path: &str) -> MyResult<File> {
+    let decoding = Decoding {
+        decompress: true,
+        ..Decoding::default()
+    };
+    let mut input = open(archive, decoding)?;
+    let path = path.trim_start_matches("./");
+    let mut member = tempfile::tempfile()?;
+
+    let head = input.fill_buf()?;
+    if is_zip(head) {
+        let mut zip = zip(&mut input)?;
+        let mut entry = zip.by_name(path).map_err(|_| ArchiveError::MissingMember)?;
+        io::copy(&mut entry, &mut member)?;
+    } else if !is_tar(head) {
+        return Err(Box::new(ArchiveError::Unknown));
+    } else {
+        let mut tar = tar::Archive::new(input);
+        let mut entries = tar.entries()?;
+        let mut entry = loop {
+            let entry = entries.next().ok_or(ArchiveError::MissingMember)??;
+            if entry.path()?.to_string_lossy().trim_start_matches("./") == path {
+                break entry;
+            }
+        };
+        io::copy(&mut entry, &mut member)?;
+    }
+
+    member.rewind()?;
+    Ok(member)
+}
@@ -0,0 +1,473 @@
+//! The line-by-line core of catr, for use on any readers and writer.
+
+use std::io::{self, BufRead, Write};
+
+use crate::eol::Eol;
+use crate::highlight::Highlighter;
+use crate::jsonl;
+use crate::reverse::{self, Separator};
+use crate::select::LineRanges;
+use crate::visualize::Visualizer;
+
+/// How each printed line is laid out
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// As it is
+    #[default]
+    Text,
+    /// As a JSON object with its file, number and text, one per line
+    Jsonl,
+}
+
+/// Which lines get a number
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Numbering {
+    #[default]
+    None,
+    /// Every line, like `-n`
+    All,
+    /// Every line that is not empty, like `-b`
+    NonBlank,
+}
+
+/// Numbering and squeezing state carried from one chunk of input to the next
+#[derive(Debug)]
+pub(crate) struct LineState {
+    line_number: usize,
+    previous_blank: bool,
+    at_line_start: bool,
+    /// Position of the current line for --lines, per file or across all files
+    pub index: u64,
+    /// Number of lines --lines counts back from, when a range needs it
+    pub total: Option<u64>,
+    pub highlighter: Option<Highlighter>,
+    /// Name of the current input, for --format jsonl
+    filename: String,
+}
+
+impl LineState {
+    fn new() -> Self {
+        LineState {
+            line_number: 1,
+            previous_blank: false,
+            at_line_start: true,
+            index: 0,
+            total: None,
+            highlighter: None,
+            filename: String::new(),
+        }
+    }
+}
+
+/// Concatenates inputs into a writer, numbering and rendering their lines as the
+/// catr binary does. Numbering carries on from one input to the next.
+///
+/// ```
+/// use catr::{Cat, Numbering};
+///
+/// let mut out = Vec::new();
+/// let mut cat = Cat::new().numbering(Numbering::NonBlank).show_ends(true);
+/// cat.cat("a.txt", &b"one\n\ntwo\n"[..], &mut out)?;
+/// cat.cat("b.txt", &b"three\n"[..], &mut out)?;
+/// assert_eq!(out, b"     1\tone$\n$\n     2\ttwo$\n     3\tthree$\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Cat {
+    numbering: Numbering,
+    number_width: usize,
+    number_separator: String,
+    number_per_input: bool,
+    squeeze_blank: bool,
+    visualizer: Visualizer,
+    lines: Option<LineRanges>,
+    format: Format,
+    /// More of the current input may still arrive, so never stop reading early
+    pub(crate) follow: bool,
+    pub(crate) state: LineState,
+}
+
+impl Default for Cat {
+    fn default() -> Self {
+        Cat {
+            numbering: Numbering::None,
+            number_width: 6,
+            number_separator: "\t".to_string(),
+            number_per_input: false,
+            squeeze_blank: false,
+            visualizer: Visualizer::default(),
+            lines: None,
+            format: Format::Text,
+            follow: false,
+            state: LineState::new(),
+        }
+    }
+}
+
+impl Cat {
+    /// A plain concatenation, until options are added
+    pub fn new() -> Cat {
+        Cat::default()
+    }
+
+    pub fn numbering(mut self, numbering: Numbering) -> Cat {
+        self.numbering = numbering;
+        self
+    }
+
+    /// Right-align line numbers in this many columns (6 by default)
+    pub fn number_width(mut self, width: usize) -> Cat {
+        self.number_width = width;
+        self
+    }
+
+    /// Put this between a line number and its line (a tab by default)
+    pub fn number_separator(mut self, separator: &str) -> Cat {
+        self.number_separator = separator.to_string();
+        self
+    }
+
+    /// Start numbering again at 1 with each input
+    pub fn number_per_input(mut self, per_input: bool) -> Cat {
+        self.number_per_input = per_input;
+        self
+    }
+
+    /// Print runs of empty lines as one
+    pub fn squeeze_blank(mut self, squeeze: bool) -> Cat {
+        self.squeeze_blank = squeeze;
+        self
+    }
+
+    /// Show each line end as `$`
+    pub fn show_ends(mut self, show: bool) -> Cat {
+        self.visualizer.ends = show;
+        self
+    }
+
+    /// Show tabs as `^I`
+    pub fn show_tabs(mut self, show: bool) -> Cat {
+        self.visualizer.tabs = show;
+        self
+    }
+
+    /// Show control and non-ASCII bytes in `^` and `M-` notation
+    pub fn show_nonprinting(mut self, show: bool) -> Cat {
+        self.visualizer.nonprinting = show;
+        self
+    }
+
+    /// End every line with `eol` instead of `\n`. Inputs are expected to use
+    /// `\n` already; [`Options::run`](crate::Options::run) converts other endings
+    /// before they get here.
+    pub fn eol(mut self, eol: Eol) -> Cat {
+        self.visualizer.eol = Some(eol);
+        self
+    }
+
+    /// Print only the lines of each input that fall in these ranges
+    pub fn lines(mut self, lines: LineRanges) -> Cat {
+        self.lines = Some(lines);
+        self
+    }
+
+    /// Print lines as text or as JSON objects
+    pub fn format(mut self, format: Format) -> Cat {
+        self.format = format;
+        self
+    }
+
+    /// The line ending set with [`Cat::eol`], which inputs are converted for
+    pub(crate) fn eol_conversion(&self) -> Option<Eol> {
+        self.visualizer.eol
+    }
+
+    /// Whether line ranges count from the end of the input
+    pub(crate) fn needs_total(&self) -> bool {
+        self.lines.as_ref().is_some_and(LineRanges::needs_total)
+    }
+
+    /// Whether the output is a byte-for-byte copy of the input
+    pub(crate) fn is_identity(&self) -> bool {
+        self.numbering == Numbering::None
+            && !self.squeeze_blank
+            && self.lines.is_none()
+            && self.format == Format::Text
+            && self.visualizer.is_identity()
+    }
+
+    /// Print all of `input`, named `name` in JSON output, after whatever was printed before
+    pub fn cat(
+        &mut self,
+        name: &str,
+        mut input: impl BufRead,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        self.begin(name);
+        self.state.index = 0;
+        if self.needs_total() {
+            // Ranges counting from the end need the whole input first
+            let mut buf = Vec::new();
+            input.read_to_end(&mut buf)?;
+            let newlines = buf.iter().filter(|&&b| b == b'\n').count();
+            let unterminated = !buf.is_empty() && !buf.ends_with(b"\n");
+            self.state.total = Some((newlines + usize::from(unterminated)) as u64);
+            return self.write_lines(&mut &buf[..], out);
+        }
+        self.write_lines(&mut input, out)
+    }
+
+    /// Print every input in turn
+    pub fn cat_all<'a, R: BufRead>(
+        &mut self,
+        inputs: impl IntoIterator<Item = (&'a str, R)>,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        for (name, input) in inputs {
+            self.cat(name, input, out)?;
+        }
+        Ok(())
+    }
+
+    /// Get ready for the next input
+    pub(crate) fn begin(&mut self, name: &str) {
+        if self.number_per_input {
+            self.state.line_number = 1;
+            self.state.previous_blank = false;
+            self.state.at_line_start = true;
+        }
+        self.state.filename.clear();
+        self.state.filename.push_str(name);
+    }
+
+    /// Print everything currently readable from `input`
+    pub(crate) fn write_lines(
+        &mut self,
+        input: &mut impl BufRead,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let state = &mut self.state;
+        // Without numbers to keep counting, nothing after the last selected line matters
+        let stop_after = match &self.lines {
+            Some(lines) if !self.follow && (self.number_per_input || self.numbering == Numbering::None) => {
+                lines.last(state.total)
+            }
+            _ => None,
+        };
+
+        let mut line = Vec::new();
+        while input.read_until(b'\n', &mut line)? > 0 {
+            // A line may arrive in pieces when following a file that is still being written
+            let at_line_start = state.at_line_start;
+            state.at_line_start = line.ends_with(b"\n");
+            if at_line_start || state.index == 0 {
+                state.index += 1;
+            }
+            if stop_after.is_some_and(|last| state.index > last) {
+                break;
+            }
+
+            let is_blank = at_line_start && line == b"\n";
+            if self.squeeze_blank && is_blank && state.previous_blank {
+                line.clear();
+                continue;
+            }
+            state.previous_blank = is_blank;
+
+            let should_number = at_line_start
+                && match self.numbering {
+                    Numbering::None => false,
+                    Numbering::All => true,
+                    Numbering::NonBlank => !is_blank,
+                };
+            // Lines outside --lines are numbered and squeezed as usual, just not printed
+            let selected = self.lines.as_ref().is_none_or(|lines| lines.contains(state.index, state.total));
+
+            if should_number && !selected {
+                state.line_number += 1;
+            }
+            if !selected {
+                line.clear();
+                continue;
+            }
+
+            if self.format == Format::Jsonl {
+                let number = should_number.then_some(state.line_number);
+                state.line_number += usize::from(should_number);
+                jsonl::write_line(out, &state.filename, number, &line)?;
+                line.clear();
+                continue;
+            }
+            if should_number {
+                write_number(
+                    out,
+                    state.line_number,
+                    self.number_width,
+                    &self.number_separator,
+                )?;
+                state.line_number += 1;
+            }
+            match &mut state.highlighter {
+                Some(highlighter) => self.visualizer.write(out, &highlighter.highlight(&line))?,
+                None => self.visualizer.write(out, &line)?,
+            }
+
+            line.clear();
+        }
+        out.flush()
+    }
+
+    /// Print the records of `file` from `start` on, last to first, numbered by their original position
+    pub(crate) fn write_reversed(
+        &mut self,
+        file: &std::fs::File,
+        start: u64,
+        separator: &Separator,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let numbering = self.numbering;
+        let is_numbered = |record: &[u8]| match numbering {
+            Numbering::None => false,
+            Numbering::All => true,
+            Numbering::NonBlank => !separator.is_blank(record),
+        };
+
+        // Numbers and --lines positions count from the front, so find out how far they go.
+        // Squeezed records take no number, so this pass squeezes the same way as the next
+        let (mut index, mut numbered): (u64, usize) = (0, 0);
+        if numbering != Numbering::None || self.lines.is_some() {
+            let mut previous_blank = self.state.previous_blank;
+            for record in reverse::records(file, start, separator)? {
+                let record = record?;
+                index += 1;
+                let is_blank = separator.is_blank(&record);
+                if self.squeeze_blank && is_blank && previous_blank {
+                    continue;
+                }
+                previous_blank = is_blank;
+                if is_numbered(&record) {
+                    numbered += 1;
+                }
+            }
+        }
+        let total = Some(index);
+        let state = &mut self.state;
+        let first_number = state.line_number;
+        state.line_number += numbered;
+
+        for record in reverse::records(file, start, separator)? {
+            let record = record?;
+            let position = index;
+            index = index.saturating_sub(1);
+
+            let is_blank = separator.is_blank(&record);
+            if self.squeeze_blank && is_blank && state.previous_blank {
+                continue;
+            }
+            state.previous_blank = is_blank;
+
+            let should_number = is_numbered(&record);
+            let number = first_number + numbered.saturating_sub(1);
+            if should_number {
+                numbered -= 1;
+            }
+            if !self.lines.as_ref().is_none_or(|lines| lines.contains(position, total)) {
+                continue;
+            }
+
+            if should_number {
+                write_number(out, number, self.number_width, &self.number_separator)?;
+            }
+            self.visualizer.write(out, &record)?;
+        }
+        state.at_line_start = true;
+        out.flush()
+    }
+}
+
+fn write_number(
+    out: &mut impl Write,
+    number: usize,
+    width: usize,
+    separator: &str,
+) -> io::Result<()> {
+    write!(out, "{:>width$}{}", number, separator, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cat, Format, Numbering};
+    use crate::Eol;
+
+    fn cat(mut cat: Cat, inputs: &[&str]) -> String {
+        let mut out = Vec::new();
+        let inputs = inputs.iter().map(|input| ("input", input.as_bytes()));
+        cat.cat_all(inputs, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(cat(Cat::new(), &["a\n", "b"]), "a\nb");
+    }
+
+    #[test]
+    fn test_numbering_carries_across_inputs() {
+        let numbered = Cat::new().numbering(Numbering::All);
+        assert_eq!(
+            cat(numbered, &["a\n\n", "b\n"]),
+            "     1\ta\n     2\t\n     3\tb\n"
+        );
+        let nonblank = Cat::new().numbering(Numbering::NonBlank);
+        assert_eq!(cat(nonblank, &["a\n\n", "b\n"]), "     1\ta\n\n     2\tb\n");
+    }
+
+    #[test]
+    fn test_unterminated_line_continues_in_next_input() {
+        let numbered = Cat::new().numbering(Numbering::All);
+        assert_eq!(cat(numbered, &["a", "b\nc\n"]), "     1\tab\n     2\tc\n");
+    }
+
+    #[test]
+    fn test_number_per_input() {
+        let numbered = Cat::new()
+            .numbering(Numbering::All)
+            .number_per_input(true)
+            .number_width(2)
+            .number_separator(": ");
+        assert_eq!(cat(numbered, &["a\n", "b\n"]), " 1: a\n 1: b\n");
+    }
+
+    #[test]
+    fn test_squeeze_and_show() {
+        let squeezed = Cat::new()
+            .squeeze_blank(true)
+            .show_ends(true)
+            .show_tabs(true);
+        assert_eq!(cat(squeezed, &["a\tb\n\n\n\nc\n"]), "a^Ib$\n$\nc$\n");
+        let nonprinting = Cat::new().show_nonprinting(true).eol(Eol::Dos);
+        assert_eq!(cat(nonprinting, &["\x01\u{e9}\n"]), "^AM-CM-)\r\n");
+    }
+
+    #[test]
+    fn test_lines() {
+        let last_two = Cat::new().lines(":-2".parse().unwrap());
+        assert_eq!(cat(last_two, &["1\n2\n3\n"]), "1\n2\n");
+        let numbered = Cat::new()
+            .numbering(Numbering::All)
+            .lines("2".parse().unwrap());
+        assert_eq!(
+            cat(numbered, &["a\nb\n", "c\nd\n"]),
+            "     2\tb\n     4\td\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let json = Cat::new().numbering(Numbering::All).format(Format::Jsonl);
+        assert_eq!(
+            cat(json, &["hi\n"]),
+            "{\"file\":\"input\",\"line\":1,\"text\":\"hi\"}\n"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use std::io::{BufRead, BufReader};
+
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+/// Compression formats recognised by their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Format {
+    pub fn detect(header: &[u8]) -> Option<Format> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Format::Gzip)
+        } else if header.starts_with(b"BZh") {
+            Some(Format::Bzip2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Format::Xz)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Format::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Wrap `reader` so that reading it yields the decompressed bytes of every concatenated member
+    pub fn decoder(self, reader: impl BufRead + 'static) -> std::io::Result<Box<dyn BufRead>> {
+        Ok(match self {
+            Format::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+            Format::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+            Format::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+            Format::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        })
+    }
+}
@@ -0,0 +1,101 @@
+//! Transcoding output from UTF-8 into another character encoding.
+
+use std::io::{self, Write};
+
+use encoding_rs::{EncoderResult, Encoding, UTF_16BE, UTF_16LE};
+
+/// Look up an encoding by any of its WHATWG labels, e.g. `latin1`, `utf-16le` or `shift_jis`
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding '{}'", label))
+}
+
+/// Encodes the UTF-8 written to it into `encoding`. Invalid UTF-8 becomes
+/// U+FFFD, and characters the encoding cannot represent become `?`.
+pub struct EncodeWriter<W: Write> {
+    inner: W,
+    encoding: &'static Encoding,
+    /// The start of a UTF-8 sequence whose remaining bytes have not been written yet
+    pending: Vec<u8>,
+}
+
+impl<W: Write> EncodeWriter<W> {
+    pub fn new(inner: W, encoding: &'static Encoding) -> Self {
+        EncodeWriter { inner, encoding, pending: Vec::new() }
+    }
+
+    fn encode(&mut self, text: &str) -> io::Result<()> {
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            // encoding_rs only decodes UTF-16, so it is encoded by hand
+            let big_endian = self.encoding == UTF_16BE;
+            let bytes: Vec<u8> = text
+                .encode_utf16()
+                .flat_map(|unit| if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() })
+                .collect();
+            return self.inner.write_all(&bytes);
+        }
+
+        let mut encoder = self.encoding.new_encoder();
+        let mut text = text;
+        let mut buf = vec![0; text.len() * 4 + 16];
+        loop {
+            let (result, read, written) = encoder.encode_from_utf8_without_replacement(text, &mut buf, true);
+            self.inner.write_all(&buf[..written])?;
+            text = &text[read..];
+            match result {
+                EncoderResult::InputEmpty => return Ok(()),
+                EncoderResult::OutputFull => buf.resize(buf.len() * 2, 0),
+                EncoderResult::Unmappable(_) => self.inner.write_all(b"?")?,
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Write out a trailing incomplete UTF-8 sequence as U+FFFD
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.encode("\u{FFFD}")?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for EncodeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(buf);
+
+        let mut rest = &bytes[..];
+        while !rest.is_empty() {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.encode(text)?;
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    // SAFETY: `from_utf8` just checked that this prefix is valid
+                    self.encode(unsafe { std::str::from_utf8_unchecked(valid) })?;
+                    match e.error_len() {
+                        Some(len) => {
+                            self.encode("\u{FFFD}")?;
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.pending = invalid.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
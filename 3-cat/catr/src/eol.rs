@@ -0,0 +1,150 @@
+//! Line-ending detection and conversion.
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+/// A line-ending convention
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Eol {
+    Unix,
+    Dos,
+    Mac,
+}
+
+impl Eol {
+    pub fn terminator(self) -> &'static [u8] {
+        match self {
+            Eol::Unix => b"\n",
+            Eol::Dos => b"\r\n",
+            Eol::Mac => b"\r",
+        }
+    }
+}
+
+/// Turns CRLF and lone CR line endings into LF as the bytes stream through
+pub struct Newlines<R> {
+    inner: R,
+    pending_cr: bool,
+}
+
+impl<R: BufRead> Newlines<R> {
+    pub fn new(inner: R) -> Self {
+        Newlines { inner, pending_cr: false }
+    }
+}
+
+impl<R: BufRead> Read for Newlines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let input = self.inner.fill_buf()?;
+        if input.is_empty() {
+            if self.pending_cr && !buf.is_empty() {
+                self.pending_cr = false;
+                buf[0] = b'\n';
+                return Ok(1);
+            }
+            return Ok(0);
+        }
+
+        let (mut read, mut written) = (0, 0);
+        while read < input.len() && written < buf.len() {
+            let c = input[read];
+            if self.pending_cr {
+                // The CR ends a line whether or not an LF follows it
+                self.pending_cr = false;
+                buf[written] = b'\n';
+                written += 1;
+                if c == b'\n' {
+                    read += 1;
+                }
+                continue;
+            }
+            if c == b'\r' {
+                self.pending_cr = true;
+            } else {
+                buf[written] = c;
+                written += 1;
+            }
+            read += 1;
+        }
+        self.inner.consume(read);
+
+        if written == 0 && !buf.is_empty() {
+            // Everything consumed was a CR still waiting for its LF
+            return self.read(buf);
+        }
+        Ok(written)
+    }
+}
+
+/// How many lines of an input end with each convention
+#[derive(Debug, Default, PartialEq)]
+pub struct EolCounts {
+    pub lf: u64,
+    pub crlf: u64,
+    pub cr: u64,
+}
+
+impl EolCounts {
+    pub fn count(input: &mut impl BufRead) -> io::Result<EolCounts> {
+        let mut counts = EolCounts::default();
+        let mut pending_cr = false;
+        loop {
+            let buf = input.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            for &c in buf {
+                match (pending_cr, c) {
+                    (true, b'\n') => counts.crlf += 1,
+                    (true, _) => counts.cr += 1,
+                    (false, b'\n') => counts.lf += 1,
+                    _ => {}
+                }
+                pending_cr = c == b'\r';
+            }
+            let len = buf.len();
+            input.consume(len);
+        }
+        if pending_cr {
+            counts.cr += 1;
+        }
+        Ok(counts)
+    }
+}
+
+impl fmt::Display for EolCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match (self.lf > 0, self.crlf > 0, self.cr > 0) {
+            (false, false, false) => "none",
+            (true, false, false) => "unix",
+            (false, true, false) => "dos",
+            (false, false, true) => "mac",
+            _ => "mixed",
+        };
+        write!(f, "{} (lf: {}, crlf: {}, cr: {})", kind, self.lf, self.crlf, self.cr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    const MIXED: &[u8] = b"unix\nmac\rdos\r\n\r\nend\r";
+
+    #[test]
+    fn test_newlines_across_buffer_boundaries() {
+        for capacity in [1, 2, 3, 64] {
+            let mut out = Vec::new();
+            Newlines::new(BufReader::with_capacity(capacity, MIXED)).read_to_end(&mut out).unwrap();
+            assert_eq!(out, b"unix\nmac\ndos\n\nend\n");
+        }
+    }
+
+    #[test]
+    fn test_count() {
+        let counts = EolCounts::count(&mut BufReader::with_capacity(1, MIXED)).unwrap();
+        assert_eq!(counts, EolCounts { lf: 1, crlf: 2, cr: 2 });
+        assert_eq!(counts.to_string(), "mixed (lf: 1, crlf: 2, cr: 2)");
+    }
+}
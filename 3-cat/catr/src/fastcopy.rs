@@ -0,0 +1,141 @@
+//! Copying an untransformed input straight to stdout or the --output file.
+//!
+//! On Linux the data is moved inside the kernel with `copy_file_range`,
+//! `sendfile` or `splice`, whichever the pair of descriptors supports.
+//! Everything else goes through a large userspace buffer.
+
+use std::io::{self, BufRead, Write};
+
+use crate::open::Input;
+
+const BUFFER_SIZE: usize = 128 * 1024;
+
+/// A writer whose descriptor the kernel can copy into once it has been flushed
+#[cfg(unix)]
+pub trait Target: Write + std::os::fd::AsRawFd {}
+#[cfg(unix)]
+impl<T: Write + std::os::fd::AsRawFd> Target for T {}
+#[cfg(not(unix))]
+pub trait Target: Write {}
+#[cfg(not(unix))]
+impl<T: Write> Target for T {}
+
+/// Which end of a copy failed
+#[derive(Debug)]
+pub enum CopyError {
+    Read(io::Error),
+    Write(io::Error),
+}
+
+/// Copy everything left in `input` to `out`, returning the number of bytes copied
+pub fn copy(input: &mut Input, out: &mut dyn Target) -> Result<u64, CopyError> {
+    out.flush().map_err(CopyError::Write)?;
+
+    // Bytes already sitting in the reader's buffer are invisible to the kernel
+    let buffered = input.fill_buf().map_err(CopyError::Read)?.len();
+    if buffered > 0 {
+        out.write_all(input.fill_buf().map_err(CopyError::Read)?)
+            .map_err(CopyError::Write)?;
+        input.consume(buffered);
+    }
+
+    // The kernel does not say which descriptor failed; a closed or full output is
+    // far more likely than a file that cannot be read any more
+    let (copied, finished) = kernel_copy(input, out).map_err(CopyError::Write)?;
+    let rest = if finished { 0 } else { buffer_copy(input, out)? };
+    Ok(buffered as u64 + copied + rest)
+}
+
+fn buffer_copy(input: &mut Input, out: &mut dyn Target) -> Result<u64, CopyError> {
+    let mut buf = vec![0; BUFFER_SIZE];
+    let mut total = 0;
+    loop {
+        let n = match io::Read::read(input, &mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(CopyError::Read(e)),
+        };
+        out.write_all(&buf[..n]).map_err(CopyError::Write)?;
+        total += n as u64;
+    }
+    out.flush().map_err(CopyError::Write)?;
+    Ok(total)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_copy(_input: &mut Input, _out: &mut dyn Target) -> io::Result<(u64, bool)> {
+    Ok((0, false))
+}
+
+/// Move bytes with the first syscall that works. Returns the bytes copied and
+/// whether EOF was reached; if not, no syscall applies and the caller must finish.
+#[cfg(target_os = "linux")]
+fn kernel_copy(input: &mut Input, out: &mut dyn Target) -> io::Result<(u64, bool)> {
+    let Some(in_fd) = input.raw_fd() else {
+        return Ok((0, false));
+    };
+    let out_fd = out.as_raw_fd();
+
+    #[derive(Clone, Copy)]
+    enum Method {
+        CopyFileRange,
+        SendFile,
+        Splice,
+    }
+
+    const CHUNK: usize = 1 << 30;
+
+    let mut total = 0;
+
+    for method in [Method::CopyFileRange, Method::SendFile, Method::Splice] {
+        loop {
+            // SAFETY: both descriptors are open for the duration of the call and
+            // null offsets make the kernel use and advance the file positions
+            let ret = unsafe {
+                match method {
+                    Method::CopyFileRange => libc::copy_file_range(
+                        in_fd,
+                        std::ptr::null_mut(),
+                        out_fd,
+                        std::ptr::null_mut(),
+                        CHUNK,
+                        0,
+                    ),
+                    Method::SendFile => libc::sendfile(out_fd, in_fd, std::ptr::null_mut(), CHUNK),
+                    Method::Splice => libc::splice(
+                        in_fd,
+                        std::ptr::null_mut(),
+                        out_fd,
+                        std::ptr::null_mut(),
+                        CHUNK,
+                        0,
+                    ),
+                }
+            };
+
+            match ret {
+                0 => return Ok((total, true)),
+                n if n > 0 => total += n as u64,
+                _ => {
+                    let err = io::Error::last_os_error();
+                    match err.raw_os_error() {
+                        Some(libc::EINTR) => continue,
+                        Some(
+                            libc::EINVAL
+                            | libc::ENOSYS
+                            | libc::EXDEV
+                            | libc::EOPNOTSUPP
+                            | libc::EBADF
+                            | libc::ESPIPE
+                            | libc::EPERM,
+                        ) => break,
+                        _ => return Err(err),
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((total, false))
+}
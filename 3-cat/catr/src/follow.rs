@@ -0,0 +1,135 @@
+//! Streaming data appended to the last input after EOF, like `tail -f`.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::open::Input;
+use crate::MyResult;
+
+#[derive(Debug, Error)]
+#[error("cannot follow a decompressed or decoded input")]
+pub struct CannotFollow;
+
+/// How often to look for rotation, and to look for new data when inotify is unavailable
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Keep passing `input` to `drain` as it grows. Truncation restarts from the
+/// beginning; when `filename` is replaced, the new file is read from the start.
+/// Only regular files are followed; stdin returns immediately, and inputs that
+/// went through a decoder are refused, since it cannot pick up where it left off.
+pub fn follow<F>(filename: &str, mut input: Input, mut drain: F) -> MyResult<()>
+where
+    F: FnMut(&mut Input) -> io::Result<()>,
+{
+    match input {
+        Input::File(_) => {}
+        Input::Stdin(_) => return Ok(()),
+        Input::Decoded(_) => return Err(Box::new(CannotFollow)),
+    }
+
+    let path = Path::new(filename);
+    let mut watcher = Watcher::new(path);
+    loop {
+        watcher.wait();
+
+        if let Some(file) = input.file_mut() {
+            if file.get_ref().metadata()?.len() < file.stream_position()? {
+                file.seek(SeekFrom::Start(0))?;
+            }
+        }
+        drain(&mut input)?;
+
+        if is_replaced(path, &mut input)? {
+            input = Input::File(BufReader::new(File::open(path)?));
+            watcher = Watcher::new(path);
+            drain(&mut input)?;
+        }
+    }
+}
+
+/// True when `path` now names a different file than the one being read
+#[cfg(unix)]
+fn is_replaced(path: &Path, input: &mut Input) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (Some(file), Ok(current)) = (input.file_mut(), fs::metadata(path)) else {
+        return Ok(false);
+    };
+    let open = file.get_ref().metadata()?;
+    Ok((open.dev(), open.ino()) != (current.dev(), current.ino()))
+}
+
+#[cfg(not(unix))]
+fn is_replaced(_path: &Path, _input: &mut Input) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Waits for a file to change, through inotify where available
+struct Watcher {
+    #[cfg(target_os = "linux")]
+    inotify: Option<std::os::fd::OwnedFd>,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    fn new(path: &Path) -> Self {
+        Watcher { inotify: inotify_watch(path) }
+    }
+
+    /// Block until the file may have changed, or at most `POLL_INTERVAL`
+    fn wait(&mut self) {
+        use std::os::fd::AsRawFd;
+
+        let Some(fd) = &self.inotify else {
+            return thread::sleep(POLL_INTERVAL);
+        };
+
+        let mut pollfd = libc::pollfd { fd: fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        // SAFETY: `pollfd` is a valid array of one element for the duration of the call
+        let ready = unsafe { libc::poll(&mut pollfd, 1, POLL_INTERVAL.as_millis() as i32) };
+        if ready > 0 {
+            // Only the wakeup matters; discard the queued events
+            let mut events = [0u8; 4096];
+            // SAFETY: the buffer is writable for its whole length and the fd is non-blocking
+            while unsafe { libc::read(fd.as_raw_fd(), events.as_mut_ptr().cast(), events.len()) } > 0 {}
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn inotify_watch(path: &Path) -> Option<std::os::fd::OwnedFd> {
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+
+    // SAFETY: plain syscall without pointer arguments
+    let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return None;
+    }
+    // SAFETY: `fd` was just returned by inotify_init1 and is owned by nobody else
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mask = libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_DELETE_SELF | libc::IN_MOVE_SELF;
+    // SAFETY: `path` is a NUL-terminated string that outlives the call
+    let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) };
+    (wd >= 0).then_some(fd)
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    fn new(_path: &Path) -> Self {
+        Watcher {}
+    }
+
+    fn wait(&mut self) {
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,93 @@
+//! An xxd-style hex dump of the input, and its reverse.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// Layout of a dump line: `columns` bytes per line, separated into groups of `group` bytes
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump {
+    pub columns: usize,
+    pub group: usize,
+}
+
+impl HexDump {
+    /// Write `input` as lines of offset, hex bytes and printable ASCII
+    pub fn dump(&self, input: &mut impl Read, out: &mut impl Write) -> io::Result<()> {
+        let groups = self.columns.div_ceil(self.group);
+        let width = self.columns * 2 + groups - 1;
+
+        let mut offset = 0;
+        let mut buf = vec![0; self.columns];
+        loop {
+            let len = read_full(input, &mut buf)?;
+            if len == 0 {
+                return Ok(());
+            }
+
+            let mut hex = String::with_capacity(width);
+            for (i, byte) in buf[..len].iter().enumerate() {
+                if i > 0 && i % self.group == 0 {
+                    hex.push(' ');
+                }
+                hex.push_str(&format!("{:02x}", byte));
+            }
+            let ascii: String = buf[..len]
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            writeln!(out, "{:08x}: {:<width$}  {}", offset, hex, ascii, width = width)?;
+            offset += len;
+        }
+    }
+}
+
+/// Fill `buf` unless EOF comes first, so short reads from pipes do not split dump lines
+fn read_full(input: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match input.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// Turn a dump made by `HexDump::dump` (or xxd) back into bytes. The offsets are
+/// ignored; the hex bytes of each line end at the two spaces before the ASCII column.
+pub fn unhex(input: &mut impl BufRead, out: &mut impl Write) -> io::Result<()> {
+    let invalid = |number, message: &str| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number, message))
+    };
+
+    for (i, line) in input.split(b'\n').enumerate() {
+        let line = line?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            return Err(invalid(i + 1, "missing offset"));
+        };
+        let data = line[colon + 1..].strip_prefix(b" ").unwrap_or(&line[colon + 1..]);
+        let hex_end = data.windows(2).position(|w| w == b"  ").unwrap_or(data.len());
+        let digits: Vec<u8> = data[..hex_end].iter().copied().filter(|&b| b != b' ').collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(invalid(i + 1, "odd number of hex digits"));
+        }
+
+        let bytes = digits
+            .chunks(2)
+            .map(|pair| {
+                std::str::from_utf8(pair)
+                    .ok()
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(|| invalid(i + 1, "invalid hex digit"))
+            })
+            .collect::<io::Result<Vec<u8>>>()?;
+        out.write_all(&bytes)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,414 @@
+//! A small keyword/string/comment highlighter for a handful of languages.
+
+use std::path::Path;
+
+const RESET: &str = "\x1b[0m";
+const KEYWORD: &str = "\x1b[35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[90m";
+
+/// What it takes to colour one language
+#[derive(Debug)]
+pub struct Language {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    /// Program names recognised in a `#!` line
+    interpreters: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [u8],
+    keywords: &'static [&'static str],
+}
+
+pub const LANGUAGES: &[Language] = &[
+    Language {
+        name: "rust",
+        extensions: &["rs"],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: b"\"",
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+    },
+    Language {
+        name: "python",
+        extensions: &["py", "pyw"],
+        interpreters: &["python", "python2", "python3"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: b"\"'",
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+    },
+    Language {
+        name: "shell",
+        extensions: &["sh", "bash", "zsh"],
+        interpreters: &["sh", "bash", "zsh", "dash", "ksh"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: b"\"'",
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "then", "until", "while",
+        ],
+    },
+    Language {
+        name: "javascript",
+        extensions: &["js", "mjs", "cjs", "ts", "jsx", "tsx"],
+        interpreters: &["node", "deno"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: b"\"'`",
+        keywords: &[
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "export",
+            "extends",
+            "false",
+            "finally",
+            "for",
+            "function",
+            "if",
+            "import",
+            "in",
+            "instanceof",
+            "let",
+            "new",
+            "null",
+            "return",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typeof",
+            "undefined",
+            "var",
+            "while",
+            "yield",
+        ],
+    },
+    Language {
+        name: "c",
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: b"\"'",
+        keywords: &[
+            "break",
+            "case",
+            "char",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "extern",
+            "float",
+            "for",
+            "goto",
+            "if",
+            "int",
+            "long",
+            "namespace",
+            "return",
+            "short",
+            "signed",
+            "sizeof",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "typedef",
+            "union",
+            "unsigned",
+            "void",
+            "volatile",
+            "while",
+        ],
+    },
+    Language {
+        name: "go",
+        extensions: &["go"],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: b"\"'`",
+        keywords: &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "false",
+            "for",
+            "func",
+            "go",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "nil",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "true",
+            "type",
+            "var",
+        ],
+    },
+    Language {
+        name: "toml",
+        extensions: &["toml"],
+        interpreters: &[],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: b"\"'",
+        keywords: &["true", "false"],
+    },
+    Language {
+        name: "json",
+        extensions: &["json"],
+        interpreters: &[],
+        line_comments: &[],
+        block_comment: None,
+        quotes: b"\"",
+        keywords: &["true", "false", "null"],
+    },
+    Language {
+        name: "yaml",
+        extensions: &["yml", "yaml"],
+        interpreters: &[],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: b"\"'",
+        keywords: &["true", "false", "null", "yes", "no"],
+    },
+];
+
+impl Language {
+    pub fn by_name(name: &str) -> Option<&'static Language> {
+        LANGUAGES.iter().find(|language| language.name == name)
+    }
+
+    /// Guess the language from the file extension, then from a `#!` first line
+    pub fn detect(filename: &str, head: &[u8]) -> Option<&'static Language> {
+        let extension = Path::new(filename).extension().and_then(|e| e.to_str());
+        if let Some(language) = extension.and_then(|e| {
+            LANGUAGES
+                .iter()
+                .find(|language| language.extensions.contains(&e))
+        }) {
+            return Some(language);
+        }
+
+        let first_line = head.strip_prefix(b"#!")?.split(|&b| b == b'\n').next()?;
+        let first_line = String::from_utf8_lossy(first_line);
+        let mut words = first_line.split_whitespace();
+        let program = match words.next()?.rsplit('/').next()? {
+            "env" => words.find(|word| !word.starts_with('-'))?,
+            program => program,
+        };
+        LANGUAGES
+            .iter()
+            .find(|language| language.interpreters.contains(&program))
+    }
+}
+
+/// Colours lines one at a time, remembering block comments that span lines
+#[derive(Debug)]
+pub struct Highlighter {
+    language: &'static Language,
+    in_block_comment: bool,
+}
+
+impl Highlighter {
+    pub fn new(language: &'static Language) -> Self {
+        Highlighter {
+            language,
+            in_block_comment: false,
+        }
+    }
+
+    /// Return `line` with ANSI colours around its tokens; the line ending is kept as is
+    pub fn highlight(&mut self, line: &[u8]) -> Vec<u8> {
+        let language = self.language;
+        let body_len = line.len()
+            - line
+                .iter()
+                .rev()
+                .take_while(|&&b| b == b'\n' || b == b'\r')
+                .count();
+        let (body, ending) = line.split_at(body_len);
+
+        let mut out = Vec::with_capacity(line.len() * 2);
+        let mut i = 0;
+        while i < body.len() {
+            let rest = &body[i..];
+
+            if let Some((start, end)) = language.block_comment {
+                if self.in_block_comment || rest.starts_with(start.as_bytes()) {
+                    let from = if self.in_block_comment {
+                        0
+                    } else {
+                        start.len()
+                    };
+                    let len = match find(&rest[from..], end.as_bytes()) {
+                        Some(pos) => {
+                            self.in_block_comment = false;
+                            from + pos + end.len()
+                        }
+                        None => {
+                            self.in_block_comment = true;
+                            rest.len()
+                        }
+                    };
+                    paint(&mut out, COMMENT, &rest[..len]);
+                    i += len;
+                    continue;
+                }
+            }
+
+            if language
+                .line_comments
+                .iter()
+                .any(|prefix| rest.starts_with(prefix.as_bytes()))
+            {
+                paint(&mut out, COMMENT, rest);
+                break;
+            }
+
+            let c = rest[0];
+            let len = if language.quotes.contains(&c) {
+                let mut len = 1;
+                while len < rest.len() && rest[len] != c {
+                    len += if rest[len] == b'\\' { 2 } else { 1 };
+                }
+                let len = (len + 1).min(rest.len());
+                paint(&mut out, STRING, &rest[..len]);
+                len
+            } else if c.is_ascii_digit() {
+                let len = rest
+                    .iter()
+                    .take_while(|&&b| is_word(b) || b == b'.')
+                    .count();
+                paint(&mut out, NUMBER, &rest[..len]);
+                len
+            } else if is_word(c) {
+                let len = rest.iter().take_while(|&&b| is_word(b)).count();
+                let word = &rest[..len];
+                if language
+                    .keywords
+                    .iter()
+                    .any(|keyword| keyword.as_bytes() == word)
+                {
+                    paint(&mut out, KEYWORD, word);
+                } else {
+                    out.extend_from_slice(word);
+                }
+                len
+            } else {
+                out.push(c);
+                1
+            };
+            i += len;
+        }
+
+        out.extend_from_slice(ending);
+        out
+    }
+}
+
+fn paint(out: &mut Vec<u8>, colour: &str, text: &[u8]) {
+    out.extend_from_slice(colour.as_bytes());
+    out.extend_from_slice(text);
+    out.extend_from_slice(RESET.as_bytes());
+}
+
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Language::detect("src/main.rs", b"").map(|l| l.name),
+            Some("rust")
+        );
+        assert_eq!(
+            Language::detect("run", b"#!/usr/bin/env python3\n").map(|l| l.name),
+            Some("python")
+        );
+        assert_eq!(
+            Language::detect("run", b"#!/bin/bash -e\n").map(|l| l.name),
+            Some("shell")
+        );
+        assert_eq!(
+            Language::detect("notes.txt", b"hello\n").map(|l| l.name),
+            None
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let mut highlighter = Highlighter::new(Language::by_name("rust").unwrap());
+        let line = highlighter.highlight(b"let x = \"a\\\"b\"; // 42\n");
+        assert_eq!(
+            String::from_utf8_lossy(&line),
+            "\x1b[35mlet\x1b[0m x = \x1b[32m\"a\\\"b\"\x1b[0m; \x1b[90m// 42\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_block_comment_spans_lines() {
+        let mut highlighter = Highlighter::new(Language::by_name("c").unwrap());
+        assert_eq!(
+            highlighter.highlight(b"x /* a\n"),
+            b"x \x1b[90m/* a\x1b[0m\n"
+        );
+        assert_eq!(
+            highlighter.highlight(b"b */ 1\n"),
+            b"\x1b[90mb */\x1b[0m \x1b[33m1\x1b[0m\n"
+        );
+    }
+}
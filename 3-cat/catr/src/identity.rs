@@ -0,0 +1,76 @@
+//! Noticing when an input is the very file the output goes to.
+
+use std::fs::{self, File, Metadata};
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("input file is output file")]
+pub struct InputIsOutput;
+
+/// The device and inode of a regular file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileId {
+    dev: u64,
+    ino: u64,
+}
+
+impl FileId {
+    #[cfg(unix)]
+    fn of(meta: &Metadata) -> Option<FileId> {
+        use std::os::unix::fs::MetadataExt;
+
+        meta.is_file().then(|| FileId {
+            dev: meta.dev(),
+            ino: meta.ino(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn of(_meta: &Metadata) -> Option<FileId> {
+        None
+    }
+
+    /// The file at `path`, if it exists and is a regular file
+    pub fn of_path(path: &str) -> Option<FileId> {
+        fs::metadata(path).ok().as_ref().and_then(FileId::of)
+    }
+
+    /// The file stdout is redirected to, if it is a regular file
+    #[cfg(unix)]
+    pub fn of_stdout() -> Option<FileId> {
+        use std::os::fd::AsFd;
+
+        let fd = io::stdout().as_fd().try_clone_to_owned().ok()?;
+        File::from(fd).metadata().ok().as_ref().and_then(FileId::of)
+    }
+
+    #[cfg(not(unix))]
+    pub fn of_stdout() -> Option<FileId> {
+        None
+    }
+
+    /// Whether reading `filename` ("-" for stdin) would read back what is written to this file.
+    /// An empty input is harmless, which is why `catr a > a` may still truncate `a` as cat does.
+    pub fn is_read_by(&self, filename: &str) -> bool {
+        let meta = match filename {
+            "-" => stdin_metadata(),
+            _ => fs::metadata(filename).ok(),
+        };
+        meta.is_some_and(|meta| meta.len() > 0 && FileId::of(&meta) == Some(*self))
+    }
+}
+
+#[cfg(unix)]
+fn stdin_metadata() -> Option<Metadata> {
+    use std::os::fd::AsFd;
+
+    let fd = io::stdin().as_fd().try_clone_to_owned().ok()?;
+    File::from(fd).metadata().ok()
+}
+
+#[cfg(not(unix))]
+fn stdin_metadata() -> Option<Metadata> {
+    None
+}
@@ -0,0 +1,80 @@
+//! JSON Lines output: one object per printed line, for tools that would rather not parse `-n` prefixes.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Write `line` (its terminator dropped) as `{"file":..,"line":..,"text":..}`.
+/// `number` is None for lines -b leaves unnumbered. Lines that are not valid
+/// UTF-8 also carry their exact bytes, base64 encoded, as `"bytes"`.
+pub fn write_line(
+    out: &mut impl Write,
+    file: &str,
+    number: Option<usize>,
+    line: &[u8],
+) -> io::Result<()> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let mut record = String::from("{\"file\":");
+    push_string(&mut record, file);
+    record.push_str(",\"line\":");
+    match number {
+        Some(number) => write!(record, "{}", number).unwrap(),
+        None => record.push_str("null"),
+    }
+    record.push_str(",\"text\":");
+    match std::str::from_utf8(line) {
+        Ok(text) => push_string(&mut record, text),
+        Err(_) => {
+            push_string(&mut record, &String::from_utf8_lossy(line));
+            record.push_str(",\"bytes\":");
+            push_string(&mut record, &STANDARD.encode(line));
+        }
+    }
+    record.push_str("}\n");
+    out.write_all(record.as_bytes())
+}
+
+/// Append `text` as a quoted JSON string
+fn push_string(record: &mut String, text: &str) {
+    record.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => record.push_str("\\\""),
+            '\\' => record.push_str("\\\\"),
+            '\n' => record.push_str("\\n"),
+            '\r' => record.push_str("\\r"),
+            '\t' => record.push_str("\\t"),
+            c if c < ' ' || c == '\u{7f}' => write!(record, "\\u{:04x}", c as u32).unwrap(),
+            c => record.push(c),
+        }
+    }
+    record.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_line;
+
+    fn record(file: &str, number: Option<usize>, line: &[u8]) -> String {
+        let mut out = Vec::new();
+        write_line(&mut out, file, number, line).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_line() {
+        assert_eq!(
+            record("fox.txt", Some(3), b"The \"quick\"\tfox\n"),
+            "{\"file\":\"fox.txt\",\"line\":3,\"text\":\"The \\\"quick\\\"\\tfox\"}\n"
+        );
+        assert_eq!(
+            record("a\\b", None, b"\x01\r\n"),
+            "{\"file\":\"a\\\\b\",\"line\":null,\"text\":\"\\u0001\\r\"}\n"
+        );
+        assert_eq!(
+            record("-", Some(1), b"caf\xe9"),
+            "{\"file\":\"-\",\"line\":1,\"text\":\"caf\u{fffd}\",\"bytes\":\"Y2Fm6Q==\"}\n"
+        );
+    }
+}
@@ -1,7 +1,40 @@
+//! A cat clone. The `catr` binary only parses its arguments into [`Options`]
+//! and runs them; programs can build their own `Options`, or use [`Cat`] to
+//! number and render lines from their own readers into their own writers.
+
 use std::error::Error;
-use clap::Parser;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use clap::{builder::{NonEmptyStringValueParser, PossibleValuesParser}, value_parser, Parser, ValueEnum};
+use thiserror::Error;
+use glob::Pattern;
+
+mod archive;
+mod cat;
+pub use cat::{Cat, Format, Numbering};
+mod decompress;
+mod encoding;
+use encoding::parse_encoding;
+use encoding_rs::Encoding;
+mod eol;
+pub use eol::Eol;
+mod fastcopy;
+mod follow;
+mod hex;
+mod highlight;
+use highlight::LANGUAGES;
+mod identity;
+mod jsonl;
+mod open;
+mod options;
+pub use options::{Color, InvalidOptions, Mode, Options, Paging};
+mod output;
+mod pager;
+mod reverse;
+pub use reverse::Separator;
+mod select;
+pub use select::{LineRanges, RangeError};
+mod visualize;
+mod walk;
+use walk::parse_glob;
 
 /// Cat in Rust
 #[derive(Debug, Parser)]
@@ -19,46 +52,386 @@ pub struct Arg {
     /// If true, number non-blank output lines
     #[arg(short = 'b', long)]
     number_nonblank: bool,
+
+    /// Restart line numbering at 1 for each file
+    #[arg(long)]
+    number_per_file: bool,
+
+    /// Minimum width of line numbers
+    #[arg(long, default_value_t = 6)]
+    number_width: usize,
+
+    /// String printed between a line number and the line
+    #[arg(long, default_value_t = String::from("\t"))]
+    number_separator: String,
+
+    /// Decompress gzip, bzip2, xz and zstd inputs; other files are copied as is
+    #[arg(short = 'z', long)]
+    decompress: bool,
+
+    /// Only print the given lines, e.g. 10:20, 100:, :-5 or 1,4:6 (negative counts from the end)
+    #[arg(long, value_name = "RANGES")]
+    lines: Option<LineRanges>,
+
+    /// Apply --lines to all inputs as one stream instead of to each file
+    #[arg(long, requires = "lines")]
+    lines_global: bool,
+
+    /// Print the lines of each file in reverse order
+    #[arg(long, conflicts_with_all = ["follow", "lines_global"])]
+    reverse: bool,
+
+    /// Record separator for --reverse instead of newline
+    #[arg(long, requires = "reverse", value_parser = NonEmptyStringValueParser::new())]
+    separator: Option<String>,
+
+    /// Interpret the --reverse separator as a regular expression; each file is then read
+    /// into memory whole, since a match can be any length
+    #[arg(long, requires = "separator")]
+    regex: bool,
+
+    /// Decode inputs from this encoding, e.g. latin1, utf-16le or shift_jis
+    /// (a UTF-8 or UTF-16 BOM at the start of an input takes precedence)
+    #[arg(long, value_name = "ENCODING", value_parser = parse_encoding)]
+    from_encoding: Option<&'static Encoding>,
+
+    /// Decode inputs that start with a UTF-16 BOM to UTF-8 (implied by the encoding options)
+    #[arg(long)]
+    detect_bom: bool,
+
+    /// Encode the output in this encoding instead of UTF-8
+    #[arg(long, value_name = "ENCODING", value_parser = parse_encoding)]
+    to_encoding: Option<&'static Encoding>,
+
+    /// Convert line endings to LF
+    #[arg(long, group = "eol")]
+    to_unix: bool,
+
+    /// Convert line endings to CRLF
+    #[arg(long, group = "eol")]
+    to_dos: bool,
+
+    /// Convert line endings to CR
+    #[arg(long, group = "eol")]
+    to_mac: bool,
+
+    /// Instead of printing the files, report which line endings each one uses
+    #[arg(long, conflicts_with = "eol")]
+    detect_eol: bool,
+
+    /// Print an xxd-style hex dump of each file
+    #[arg(long, conflicts_with_all = [
+        "number", "number_nonblank", "reverse", "lines", "detect_eol", "from_encoding", "detect_bom",
+    ])]
+    hex: bool,
+
+    /// Bytes per line in --hex mode
+    #[arg(long, default_value_t = 16, value_parser = value_parser!(u16).range(1..=256))]
+    hex_columns: u16,
+
+    /// Bytes per group in --hex mode
+    #[arg(long, default_value_t = 2, value_parser = value_parser!(u16).range(1..=256))]
+    hex_group: u16,
+
+    /// Read every file under directory arguments, in name order
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// With -r, read only files matching this glob; patterns with a / match the path below the directory
+    #[arg(long, value_name = "GLOB", requires = "recursive", value_parser = parse_glob)]
+    include: Vec<Pattern>,
+
+    /// With -r, skip files and directories matching this glob
+    #[arg(long, value_name = "GLOB", requires = "recursive", value_parser = parse_glob)]
+    exclude: Vec<Pattern>,
+
+    /// Print a ==> name <== line before each file
+    #[arg(long, conflicts_with_all = ["format", "list_archive", "detect_eol"])]
+    header: bool,
+
+    /// Print the names of the members of tar and zip archives instead of their contents
+    #[arg(long, conflicts_with_all = [
+        "number", "number_nonblank", "reverse", "lines", "detect_eol", "hex", "unhex", "follow",
+    ])]
+    list_archive: bool,
+
+    /// Turn a --hex (or xxd) dump back into the bytes it shows
+    #[arg(long, conflicts_with_all = [
+        "hex", "number", "number_nonblank", "reverse", "lines", "detect_eol", "from_encoding", "detect_bom",
+    ])]
+    unhex: bool,
+
+    /// Colour keywords, strings, numbers and comments of source files
+    #[arg(long, conflicts_with_all = [
+        "show_all", "show_ends_nonprinting", "show_ends", "show_tabs_nonprinting", "show_tabs",
+        "show_nonprinting", "reverse", "hex", "unhex", "detect_eol",
+    ])]
+    highlight: bool,
+
+    /// Language for --highlight instead of guessing from the extension or #! line
+    #[arg(long, requires = "highlight", value_parser = PossibleValuesParser::new(LANGUAGES.iter().map(|l| l.name)))]
+    language: Option<String>,
+
+    /// When --highlight emits colours; auto means only on a terminal and without NO_COLOR
+    #[arg(long, value_enum, default_value_t = ColorArg::Auto)]
+    color: ColorArg,
+
+    /// Write to FILE instead of stdout, replacing it only once everything was written
+    #[arg(short, long, value_name = "FILE", conflicts_with = "follow")]
+    output: Option<String>,
+
+    /// Print lines as they are, or as JSON objects with their file and number (-b leaves blank lines null)
+    #[arg(long, value_enum, default_value_t = FormatArg::Text, conflicts_with_all = [
+        "show_all", "show_ends_nonprinting", "show_ends", "show_tabs_nonprinting", "show_tabs",
+        "show_nonprinting", "highlight", "reverse", "hex", "unhex", "detect_eol", "list_archive",
+        "to_encoding", "follow",
+    ])]
+    format: FormatArg,
+
+    /// After the last file ends, keep printing data appended to it
+    #[arg(short, long, conflicts_with_all = ["eol", "from_encoding", "to_encoding", "detect_bom"])]
+    follow: bool,
+
+    /// Show output on a terminal a screen at a time; auto pages once it no longer fits on one
+    #[arg(long, value_enum, default_value_t = PagingArg::Never)]
+    paging: PagingArg,
+
+    /// Suppress repeated empty output lines
+    #[arg(short, long)]
+    squeeze_blank: bool,
+
+    /// Equivalent to -vET
+    #[arg(short = 'A', long)]
+    show_all: bool,
+
+    /// Equivalent to -vE
+    #[arg(short = 'e')]
+    show_ends_nonprinting: bool,
+
+    /// Display $ at end of each line
+    #[arg(short = 'E', long)]
+    show_ends: bool,
+
+    /// Equivalent to -vT
+    #[arg(short = 't')]
+    show_tabs_nonprinting: bool,
+
+    /// Display TAB characters as ^I
+    #[arg(short = 'T', long)]
+    show_tabs: bool,
+
+    /// Use ^ and M- notation, except for LFD and TAB
+    #[arg(short = 'v', long)]
+    show_nonprinting: bool,
+
+    /// Always go through the line-by-line loop, even when no flag transforms the output
+    #[arg(long, hide = true)]
+    no_fast_path: bool,
+}
+
+impl Arg {
+    /// Expand the -A, -e and -t shortcuts into the flags they stand for
+    fn normalize(self) -> Self {
+        Arg {
+            // Every JSON line has a number unless -b asks for blank ones to go without
+            number: self.number || self.format == FormatArg::Jsonl && !self.number_nonblank,
+            show_nonprinting: self.show_nonprinting || self.show_all || self.show_ends_nonprinting || self.show_tabs_nonprinting,
+            show_ends: self.show_ends || self.show_all || self.show_ends_nonprinting,
+            show_tabs: self.show_tabs || self.show_all || self.show_tabs_nonprinting,
+            ..self
+        }
+    }
+
+    fn eol(&self) -> Option<Eol> {
+        match (self.to_unix, self.to_dos, self.to_mac) {
+            (true, _, _) => Some(Eol::Unix),
+            (_, true, _) => Some(Eol::Dos),
+            (_, _, true) => Some(Eol::Mac),
+            _ => None,
+        }
+    }
+
+    fn separator(&self) -> MyResult<Separator> {
+        Ok(match &self.separator {
+            None => Separator::Literal(b"\n".to_vec()),
+            Some(separator) if self.regex => Separator::Regex(regex::bytes::Regex::new(separator)?),
+            Some(separator) => Separator::Literal(separator.as_bytes().to_vec()),
+        })
+    }
+
+    /// The line-by-line options, for the library side of catr
+    fn builder(&self) -> Cat {
+        let numbering = if self.number {
+            Numbering::All
+        } else if self.number_nonblank {
+            Numbering::NonBlank
+        } else {
+            Numbering::None
+        };
+        let mut cat = Cat::new()
+            .numbering(numbering)
+            .number_width(self.number_width)
+            .number_separator(&self.number_separator)
+            .number_per_input(self.number_per_file)
+            .squeeze_blank(self.squeeze_blank)
+            .show_nonprinting(self.show_nonprinting)
+            .show_ends(self.show_ends)
+            .show_tabs(self.show_tabs)
+            .format(self.format.into());
+        if let Some(eol) = self.eol() {
+            cat = cat.eol(eol);
+        }
+        if let Some(lines) = &self.lines {
+            cat = cat.lines(lines.clone());
+        }
+        cat
+    }
+
+    /// The run these arguments ask for
+    fn options(self) -> MyResult<Options> {
+        let mode = if self.list_archive {
+            Mode::ListArchive
+        } else if self.detect_eol {
+            Mode::DetectEol
+        } else if self.hex {
+            Mode::Hex { columns: self.hex_columns.into(), group: self.hex_group.into() }
+        } else if self.unhex {
+            Mode::Unhex
+        } else {
+            Mode::Cat
+        };
+        let reverse = if self.reverse { Some(self.separator()?) } else { None };
+        Ok(Options {
+            cat: self.builder(),
+            mode,
+            reverse,
+            files: self.files,
+            recursive: self.recursive,
+            include: self.include,
+            exclude: self.exclude,
+            decompress: self.decompress,
+            from_encoding: self.from_encoding,
+            to_encoding: self.to_encoding,
+            detect_bom: self.detect_bom,
+            lines_global: self.lines_global,
+            highlight: self.highlight,
+            language: self.language,
+            color: self.color.into(),
+            header: self.header,
+            output: self.output.map(Into::into),
+            paging: self.paging.into(),
+            follow: self.follow,
+            fast_path: !self.no_fast_path,
+        })
+    }
+}
+
+// The command line spellings of the library's enums, which do not depend on clap
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ColorArg {
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<ColorArg> for Color {
+    fn from(color: ColorArg) -> Self {
+        match color {
+            ColorArg::Auto => Color::Auto,
+            ColorArg::Always => Color::Always,
+            ColorArg::Never => Color::Never,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum FormatArg {
+    Text,
+    Jsonl,
+}
+
+impl From<FormatArg> for Format {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Text => Format::Text,
+            FormatArg::Jsonl => Format::Jsonl,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum PagingArg {
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<PagingArg> for Paging {
+    fn from(paging: PagingArg) -> Self {
+        match paging {
+            PagingArg::Auto => Paging::Auto,
+            PagingArg::Always => Paging::Always,
+            PagingArg::Never => Paging::Never,
+        }
+    }
 }
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+#[derive(Debug, Error)]
+pub enum BadFileError {
+    #[error("{filename}: {from}")]
+    Open {
+        filename: String,
+        #[source]
+        from: Box<dyn Error>,
+    },
+}
+
+/// Returned by [`Options::run`] after all inputs were processed if some of them failed
+/// Each of them was already reported on stderr as it came up
+#[derive(Debug, Error)]
+#[error("{} input file(s) could not be read", .0.len())]
+pub struct FailedFiles(pub Vec<BadFileError>);
+
 pub fn get_args() -> MyResult<Arg> {
-    let args = Arg::parse();
-    Ok(args)
+    Ok(Arg::parse())
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new( File::open(filename)?))),
-    }
+fn report(failed: &mut Vec<BadFileError>, filename: &str, from: Box<dyn Error>) {
+    let err = BadFileError::Open {
+        filename: filename.to_string(),
+        from,
+    };
+    eprintln!("catr: {}", err);
+    failed.push(err);
 }
 
 pub fn run(arg : Arg) -> MyResult<()> {
-    for filename in arg.files {
-        match open(&filename) {
-            Err(err) => eprintln!("Error opening file {}: {}", filename, err),
-            Ok(file) => {
-                let mut line_number = 1;
-                for line in file.lines() {
-                    let line: String = line?;
-                    let should_number = arg.number || arg.number_nonblank && !line.is_empty();
-
-                    let numbering = if should_number {
-                        &format!("{:>6}\t", line_number)
-                    } else {
-                        ""
-                    };
-
-                    println!("{}{}", numbering, line);
-
-                    if should_number {
-                        line_number += 1;
-                    }
-                }
-            }
-        }
+    arg.normalize().options()?.run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arg;
+    use clap::Parser;
+
+    #[test]
+    fn test_normalize() {
+        let parse = |args: &[&str]| Arg::try_parse_from([&["catr"], args].concat()).unwrap().normalize();
+
+        let arg = parse(&["-A"]);
+        assert!(arg.show_nonprinting && arg.show_ends && arg.show_tabs);
+        let arg = parse(&["-e"]);
+        assert!(arg.show_nonprinting && arg.show_ends && !arg.show_tabs);
+        let arg = parse(&["-t"]);
+        assert!(arg.show_nonprinting && !arg.show_ends && arg.show_tabs);
+        let arg = parse(&["-E"]);
+        assert!(!arg.show_nonprinting && arg.show_ends && !arg.show_tabs);
+
+        // Normalizing again changes nothing
+        let arg = parse(&["-e"]).normalize();
+        assert!(arg.show_nonprinting && arg.show_ends && !arg.show_tabs);
     }
-    Ok(())
 }
@@ -1,6 +1,9 @@
 fn main() {
     if let Err(e) = catr::get_args().and_then(catr::run) {
-        eprintln!("Error: {}", e);
+        // Inputs that could not be read were already reported one by one
+        if !e.is::<catr::FailedFiles>() {
+            eprintln!("Error: {}", e);
+        }
         std::process::exit(1);
     }
 }
@@ -0,0 +1,160 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, StdinLock};
+
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use thiserror::Error;
+
+use crate::archive;
+use crate::decompress::Format;
+use crate::MyResult;
+
+#[derive(Debug, Error)]
+#[error("Is a directory")]
+pub struct IsADirectory;
+
+/// How to turn the bytes of a file into the bytes that get printed
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Decoding {
+    /// Decompress inputs whose first bytes identify a known compression format
+    pub decompress: bool,
+    /// Decode inputs from this encoding to UTF-8, unless they start with a BOM
+    pub encoding: Option<&'static Encoding>,
+    /// Also drop UTF-8 BOMs, because the output is being transcoded
+    pub strip_bom: bool,
+    /// Decode inputs starting with a UTF-16 BOM to UTF-8; otherwise their bytes pass through
+    pub detect_bom: bool,
+}
+
+/// An opened input, kept concrete so the fast path can reach the file descriptor
+pub enum Input {
+    Stdin(StdinLock<'static>),
+    File(BufReader<File>),
+    /// Bytes produced by a decoder rather than read straight from a descriptor
+    Decoded(Box<dyn BufRead>),
+}
+
+impl Input {
+    /// The underlying file, unless this input is stdin or decoded
+    pub fn file_mut(&mut self) -> Option<&mut BufReader<File>> {
+        match self {
+            Input::File(file) => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Make sure this input is a file that can be rewound, spooling it into an
+    /// anonymous temporary file if needed, and return the current position in it
+    pub fn make_seekable(&mut self) -> io::Result<u64> {
+        if let Some(Ok(start)) = self.file_mut().map(|file| file.stream_position()) {
+            return Ok(start);
+        }
+
+        let mut spool = tempfile::tempfile()?;
+        io::copy(self, &mut spool)?;
+        spool.rewind()?;
+        *self = Input::File(BufReader::new(spool));
+        Ok(0)
+    }
+
+    /// Count the lines left in this input without consuming them
+    pub fn count_lines(&mut self) -> io::Result<u64> {
+        let start = self.make_seekable()?;
+
+        let mut lines = 0;
+        let mut last = b'\n';
+        loop {
+            let buf = self.fill_buf()?;
+            let Some(&end) = buf.last() else { break };
+            lines += buf.iter().filter(|&&b| b == b'\n').count() as u64;
+            last = end;
+            let len = buf.len();
+            self.consume(len);
+        }
+        if last != b'\n' {
+            lines += 1;
+        }
+
+        if let Some(file) = self.file_mut() {
+            file.seek(SeekFrom::Start(start))?;
+        }
+        Ok(lines)
+    }
+
+    /// The descriptor whose contents are exactly what this input yields, if there is one
+    #[cfg(unix)]
+    pub fn raw_fd(&self) -> Option<std::os::fd::RawFd> {
+        use std::os::fd::AsRawFd;
+
+        match self {
+            Input::Stdin(stdin) => Some(stdin.as_raw_fd()),
+            Input::File(file) => Some(file.get_ref().as_raw_fd()),
+            Input::Decoded(_) => None,
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Stdin(stdin) => stdin.read(buf),
+            Input::File(file) => file.read(buf),
+            Input::Decoded(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Input::Stdin(stdin) => stdin.fill_buf(),
+            Input::File(file) => file.fill_buf(),
+            Input::Decoded(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Input::Stdin(stdin) => stdin.consume(amt),
+            Input::File(file) => file.consume(amt),
+            Input::Decoded(reader) => reader.consume(amt),
+        }
+    }
+}
+
+/// Open `filename`, or the member of an archive it names as `archive:path`,
+/// decoding it as `decoding` asks.
+pub fn open(filename: &str, decoding: Decoding) -> MyResult<Input> {
+    let mut input = match filename {
+        "-" => Input::Stdin(io::stdin().lock()),
+        _ => match archive::split(filename) {
+            Some((archive, path)) => Input::File(BufReader::new(archive::extract(archive, path)?)),
+            None => {
+                let file = File::open(filename)?;
+                if file.metadata()?.is_dir() {
+                    return Err(Box::new(IsADirectory));
+                }
+                Input::File(BufReader::new(file))
+            }
+        },
+    };
+
+    if decoding.decompress {
+        if let Some(format) = Format::detect(input.fill_buf()?) {
+            input = Input::Decoded(format.decoder(input)?);
+        }
+    }
+
+    let head = input.fill_buf()?;
+    let utf16_bom = head.starts_with(&[0xff, 0xfe]) || head.starts_with(&[0xfe, 0xff]);
+    if decoding.detect_bom && utf16_bom || decoding.encoding.is_some() || decoding.strip_bom {
+        let reader = DecodeReaderBytesBuilder::new()
+            .encoding(decoding.encoding)
+            .bom_override(true)
+            .utf8_passthru(true)
+            .strip_bom(true)
+            .build(input);
+        input = Input::Decoded(Box::new(BufReader::new(reader)));
+    }
+    Ok(input)
+}
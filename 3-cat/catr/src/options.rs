@@ -0,0 +1,543 @@
+//! Everything the catr binary does around the line-by-line core: finding and
+//! opening the inputs, dumping, listing, reversing and following them, and
+//! writing to the pager or an output file.
+
+use std::env;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use encoding_rs::Encoding;
+use glob::Pattern;
+use thiserror::Error;
+
+use crate::cat::Cat;
+use crate::eol::{EolCounts, Newlines};
+use crate::hex::{self, HexDump};
+use crate::highlight::{Highlighter, Language};
+use crate::identity::{FileId, InputIsOutput};
+use crate::open::{open, Decoding, Input};
+use crate::output::{AtomicFile, Output, Sink};
+use crate::reverse::Separator;
+use crate::walk::{self, Filter};
+use crate::{archive, follow, pager, report, BadFileError, FailedFiles, MyResult};
+
+/// What is printed for each input
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Its lines, laid out as the [`Cat`] options say
+    #[default]
+    Cat,
+    /// How many lines end in LF, CRLF and CR
+    DetectEol,
+    /// An xxd-style hex dump of its bytes, `columns` to a line in groups of `group`;
+    /// neither may be 0
+    Hex { columns: usize, group: usize },
+    /// The bytes shown by the hex dump it holds
+    Unhex,
+    /// The names of its members, for tar and zip archives
+    ListArchive,
+}
+
+/// When highlighted output is coloured
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// Only on a terminal, and unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// When output on a terminal goes through the built-in pager
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Paging {
+    /// Once it no longer fits on one screen
+    Auto,
+    /// Even when it fits on one screen
+    Always,
+    #[default]
+    Never,
+}
+
+/// Options that no command line could produce, which `Options::run` refuses
+#[derive(Debug, Error, PartialEq)]
+pub enum InvalidOptions {
+    #[error("the separator for reverse is empty")]
+    EmptySeparator,
+    #[error("hex dumps need at least one byte per line and per group")]
+    EmptyHexGroup,
+}
+
+/// A whole catr run: which inputs to read, what to print for each and where to.
+/// The defaults copy stdin to stdout.
+///
+/// ```no_run
+/// use catr::{Cat, Numbering, Options};
+///
+/// let options = Options {
+///     files: vec!["a.txt".to_string(), "b.txt".to_string()],
+///     cat: Cat::new().numbering(Numbering::All),
+///     output: Some("numbered.txt".into()),
+///     ..Options::default()
+/// };
+/// options.run()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct Options {
+    /// Files to read; `-` is stdin and `archive:path` a member of a tar or zip archive
+    pub files: Vec<String>,
+    /// How the lines of each input are printed
+    pub cat: Cat,
+    pub mode: Mode,
+    /// Read every file under directories in `files`, in name order
+    pub recursive: bool,
+    /// With `recursive`, read only files matching one of these, if there are any
+    pub include: Vec<Pattern>,
+    /// With `recursive`, skip files and directories matching any of these
+    pub exclude: Vec<Pattern>,
+    /// Decompress gzip, bzip2, xz and zstd inputs
+    pub decompress: bool,
+    /// Decode inputs from this encoding
+    pub from_encoding: Option<&'static Encoding>,
+    /// Encode the output in this encoding instead of UTF-8
+    pub to_encoding: Option<&'static Encoding>,
+    /// Decode inputs that start with a UTF-16 BOM, as the encodings above also do
+    pub detect_bom: bool,
+    /// Print the records of each input last to first, each ending at this separator
+    pub reverse: Option<Separator>,
+    /// Apply the line ranges of `cat` to all inputs as one stream
+    pub lines_global: bool,
+    /// Colour keywords, strings, numbers and comments of source files
+    pub highlight: bool,
+    /// Highlight as this language instead of guessing; unknown names turn highlighting off
+    pub language: Option<String>,
+    pub color: Color,
+    /// Print a `==> name <==` line before each input
+    pub header: bool,
+    /// Write here instead of stdout, replacing the file only once everything was written
+    pub output: Option<PathBuf>,
+    pub paging: Paging,
+    /// After the last input ends, keep printing data appended to it
+    pub follow: bool,
+    /// Let the kernel copy inputs that nothing transforms
+    pub fast_path: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            files: vec!["-".to_string()],
+            cat: Cat::new(),
+            mode: Mode::Cat,
+            recursive: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            decompress: false,
+            from_encoding: None,
+            to_encoding: None,
+            detect_bom: false,
+            reverse: None,
+            lines_global: false,
+            highlight: false,
+            language: None,
+            color: Color::Auto,
+            header: false,
+            output: None,
+            paging: Paging::Never,
+            follow: false,
+            fast_path: true,
+        }
+    }
+}
+
+impl Options {
+    /// Print every input, reporting those that cannot be read on stderr and
+    /// returning [`FailedFiles`] for them once the others were printed
+    pub fn run(mut self) -> MyResult<()> {
+        self.validate()?;
+        match self.cat_files() {
+            // Quitting the pager early is not a failure
+            Err(e) if pager::is_closed(&*e) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvalidOptions> {
+        if let Some(Separator::Literal(separator)) = &self.reverse {
+            if separator.is_empty() {
+                return Err(InvalidOptions::EmptySeparator);
+            }
+        }
+        if let Mode::Hex { columns, group } = self.mode {
+            if columns == 0 || group == 0 {
+                return Err(InvalidOptions::EmptyHexGroup);
+            }
+        }
+        Ok(())
+    }
+
+    fn cat_files(&mut self) -> MyResult<()> {
+        let mut failed = Vec::new();
+        let files = self.inputs(&mut failed);
+        let (sink, target) = match &self.output {
+            Some(path) => {
+                // Refuse before writing anything, rather than replace the file without its own contents
+                let target = path.to_str().and_then(FileId::of_path);
+                if let Some(filename) = files
+                    .iter()
+                    .find(|f| target.is_some_and(|t| t.is_read_by(f)))
+                {
+                    return Err(Box::new(BadFileError::Open {
+                        filename: filename.clone(),
+                        from: Box::new(InputIsOutput),
+                    }));
+                }
+                let file = AtomicFile::create(path).map_err(|e| BadFileError::Open {
+                    filename: path.display().to_string(),
+                    from: Box::new(e),
+                })?;
+                (Sink::File(file), None)
+            }
+            None => (Sink::stdout(self.paging()), FileId::of_stdout()),
+        };
+        let mut out = Output::new(sink, self.to_encoding);
+        let mut cat = mem::take(&mut self.cat);
+        cat.follow = self.follow;
+
+        let needs_total = cat.needs_total() && self.reverse.is_none();
+        let mut inputs: Vec<Option<MyResult<Input>>> = files.iter().map(|_| None).collect();
+        if needs_total && self.lines_global {
+            // Counting from the end of everything means opening and counting every input first
+            let mut total = 0;
+            for (input, filename) in inputs.iter_mut().zip(&files) {
+                let mut opened = open(filename, self.decoding());
+                if let Ok(file) = &mut opened {
+                    // An input that cannot be counted is reported when its turn comes
+                    match file.count_lines() {
+                        Ok(lines) => total += lines,
+                        Err(e) => opened = Err(Box::new(e)),
+                    }
+                }
+                *input = Some(opened);
+            }
+            cat.state.total = Some(total);
+        }
+
+        for (i, filename) in files.iter().enumerate() {
+            // Appending a file to itself would keep reading what was just written
+            if target.is_some_and(|target| target.is_read_by(filename)) {
+                report(&mut failed, filename, Box::new(InputIsOutput));
+                continue;
+            }
+            let opened = inputs[i].take().unwrap_or_else(|| open(filename, self.decoding()));
+            if self.header && opened.is_ok() {
+                writeln!(out, "==> {} <==", filename)?;
+            }
+            let file = match opened {
+                Ok(file) => file,
+                Err(from) => {
+                    report(&mut failed, filename, from);
+                    continue;
+                }
+            };
+            let last = i == files.len() - 1;
+            if let Err(from) = self.cat_input(&mut cat, filename, file, &mut out, files.len(), last) {
+                // Only a failing output stops the run; a bad input just gets skipped
+                if out.failed() {
+                    return Err(from);
+                }
+                report(&mut failed, filename, from);
+            }
+        }
+
+        out.finish()?;
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(FailedFiles(failed)))
+        }
+    }
+
+    /// Print what `mode` asks for of one input, `last` of `count`
+    fn cat_input(
+        &self,
+        cat: &mut Cat,
+        filename: &str,
+        mut file: Input,
+        out: &mut Output,
+        count: usize,
+        last: bool,
+    ) -> MyResult<()> {
+        match self.mode {
+            Mode::ListArchive => {
+                for name in archive::list(&mut file)? {
+                    // Several archives are listed in the archive:path form that prints a member
+                    if count > 1 {
+                        write!(out, "{}:", filename)?;
+                    }
+                    writeln!(out, "{}", name)?;
+                }
+            }
+            Mode::DetectEol => {
+                writeln!(out, "{}: {}", filename, EolCounts::count(&mut file)?)?;
+            }
+            Mode::Hex { columns, group } => {
+                HexDump { columns, group }.dump(&mut file, out)?;
+            }
+            Mode::Unhex => {
+                hex::unhex(&mut file, out)?;
+            }
+            Mode::Cat => {
+                if cat.eol_conversion().is_some() {
+                    // Split lines on any ending, so the loop below sees LF only
+                    file = Input::Decoded(Box::new(BufReader::new(Newlines::new(file))));
+                }
+                cat.begin(filename);
+                cat.state.highlighter = self.highlighter(filename, &mut file)?;
+                if !self.lines_global {
+                    cat.state.index = 0;
+                    if cat.needs_total() && self.reverse.is_none() {
+                        cat.state.total = Some(file.count_lines()?);
+                    }
+                }
+                match &self.reverse {
+                    Some(separator) => self.cat_reverse(cat, &mut file, separator, out)?,
+                    None => self.cat(cat, &mut file, out)?,
+                }
+
+                if self.follow && last {
+                    follow::follow(filename, file, |file| self.cat(cat, file, out))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Print everything currently readable from `file`
+    fn cat(&self, cat: &mut Cat, file: &mut Input, out: &mut Output) -> io::Result<()> {
+        if cat.is_identity() && self.is_plain() && self.fast_path {
+            if let Some(copied) = out.copy(file) {
+                copied?;
+                return Ok(());
+            }
+        }
+        cat.write_lines(file, out)
+    }
+
+    /// Print the records of `file` last to first, numbered by their original position
+    fn cat_reverse(
+        &self,
+        cat: &mut Cat,
+        file: &mut Input,
+        separator: &Separator,
+        out: &mut Output,
+    ) -> io::Result<()> {
+        let start = file.make_seekable()?;
+        match file.file_mut() {
+            Some(reader) => cat.write_reversed(reader.get_ref(), start, separator, out),
+            None => Ok(()),
+        }
+    }
+
+    /// True when nothing but the line-by-line options could change the bytes of the inputs
+    fn is_plain(&self) -> bool {
+        !(self.reverse.is_some() || self.use_color())
+            && self.from_encoding.is_none()
+            && self.to_encoding.is_none()
+    }
+
+    fn use_color(&self) -> bool {
+        self.highlight
+            && match self.color {
+                Color::Always => true,
+                Color::Never => false,
+                // Colours are for a terminal to show, not for an --output file
+                Color::Auto => {
+                    self.output.is_none()
+                        && io::stdout().is_terminal()
+                        && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                }
+            }
+    }
+
+    /// The files to read: `files`, with directories expanded when recursive
+    fn inputs(&self, failed: &mut Vec<BadFileError>) -> Vec<String> {
+        if !self.recursive {
+            return self.files.clone();
+        }
+        let filter = Filter {
+            include: &self.include,
+            exclude: &self.exclude,
+        };
+        let mut files = Vec::new();
+        for filename in &self.files {
+            if filename != "-" && Path::new(filename).is_dir() {
+                if let Err(e) = walk::walk(filename, &filter, &mut files) {
+                    report(failed, filename, Box::new(e));
+                }
+            } else {
+                files.push(filename.clone());
+            }
+        }
+        files
+    }
+
+    /// Whether output goes through the built-in pager, and if so whether even when it is short
+    fn paging(&self) -> Option<bool> {
+        if self.paging == Paging::Never || self.follow || !io::stdout().is_terminal() {
+            return None;
+        }
+        // Input typed at the terminal would compete with the pager for the keyboard
+        let typed = self.files.iter().any(|f| f == "-") && io::stdin().is_terminal();
+        match self.paging {
+            Paging::Always => Some(true),
+            _ => (!typed).then_some(false),
+        }
+    }
+
+    /// A highlighter for `file`, if colours are on and its language is known
+    fn highlighter(&self, filename: &str, file: &mut Input) -> io::Result<Option<Highlighter>> {
+        if !self.use_color() {
+            return Ok(None);
+        }
+        let language = match &self.language {
+            Some(name) => Language::by_name(name),
+            None => Language::detect(filename, file.fill_buf()?),
+        };
+        Ok(language.map(Highlighter::new))
+    }
+
+    fn decoding(&self) -> Decoding {
+        match self.mode {
+            // Archives are listed from their raw bytes, whatever they are compressed with
+            Mode::ListArchive => Decoding {
+                decompress: true,
+                ..Decoding::default()
+            },
+            // Dumps show, and undumps read, the bytes as they are, BOM or not
+            Mode::Hex { .. } | Mode::Unhex => Decoding {
+                decompress: self.decompress,
+                ..Decoding::default()
+            },
+            Mode::Cat | Mode::DetectEol => Decoding {
+                decompress: self.decompress,
+                encoding: self.from_encoding,
+                strip_bom: self.to_encoding.is_some(),
+                detect_bom: self.detect_bom || self.from_encoding.is_some() || self.to_encoding.is_some(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InvalidOptions, Mode, Options};
+    use crate::{Cat, FailedFiles, Numbering, Separator};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Run `options` with its output going to a file, and return what was written
+    fn run(dir: &Path, options: Options) -> (String, Option<usize>) {
+        let output = dir.join("out.txt");
+        let failed = Options {
+            output: Some(output.clone()),
+            ..options
+        }
+        .run()
+        .err()
+        .map(|e| e.downcast::<FailedFiles>().unwrap().0.len());
+        (fs::read_to_string(output).unwrap(), failed)
+    }
+
+    fn write(dir: &Path, name: &str, contents: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("catr-options-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = temp_dir("run");
+        let a = write(&dir, "a.txt", "one\ntwo\n");
+        let b = write(&dir, "b.txt", "three\n");
+        let missing = dir.join("missing.txt").to_str().unwrap().to_string();
+
+        let options = Options {
+            files: vec![a.clone(), missing, b.clone()],
+            cat: Cat::new().numbering(Numbering::All),
+            ..Options::default()
+        };
+        let (out, failed) = run(&dir, options);
+        assert_eq!(out, "     1\tone\n     2\ttwo\n     3\tthree\n");
+        assert_eq!(failed, Some(1));
+
+        let options = Options {
+            files: vec![a.clone(), b.clone()],
+            reverse: Some(Separator::Literal(b"\n".to_vec())),
+            header: true,
+            ..Options::default()
+        };
+        let (out, failed) = run(&dir, options);
+        assert_eq!(out, format!("==> {} <==\ntwo\none\n==> {} <==\nthree\n", a, b));
+        assert_eq!(failed, None);
+
+        let options = Options {
+            files: vec![b.clone()],
+            mode: Mode::Hex { columns: 4, group: 2 },
+            ..Options::default()
+        };
+        let (out, _) = run(&dir, options);
+        assert_eq!(out, "00000000: 7468 7265  thre\n00000004: 650a       e.\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = |options: Options| {
+            *options.run().unwrap_err().downcast::<InvalidOptions>().unwrap()
+        };
+
+        let options = Options {
+            reverse: Some(Separator::Literal(Vec::new())),
+            ..Options::default()
+        };
+        assert_eq!(invalid(options), InvalidOptions::EmptySeparator);
+        for (columns, group) in [(0, 2), (16, 0)] {
+            let options = Options {
+                mode: Mode::Hex { columns, group },
+                ..Options::default()
+            };
+            assert_eq!(invalid(options), InvalidOptions::EmptyHexGroup);
+        }
+    }
+
+    #[test]
+    fn test_output_is_input() {
+        let dir = temp_dir("output");
+        let a = write(&dir, "out.txt", "one\n");
+        let options = Options {
+            files: vec![a],
+            ..Options::default()
+        };
+        let output = dir.join("out.txt");
+        let err = Options {
+            output: Some(output.clone()),
+            ..options
+        }
+        .run()
+        .unwrap_err();
+        assert!(err.to_string().ends_with("input file is output file"), "{}", err);
+        assert_eq!(fs::read_to_string(output).unwrap(), "one\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
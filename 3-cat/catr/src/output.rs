@@ -0,0 +1,219 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::{Path, PathBuf};
+
+use encoding_rs::Encoding;
+use tempfile::{NamedTempFile, TempPath};
+
+use crate::encoding::EncodeWriter;
+use crate::fastcopy::{self, CopyError, Target};
+use crate::open::Input;
+use crate::pager::Pager;
+
+/// Where the output goes, remembering whether writing to it ever failed
+pub struct Output {
+    writer: Writer,
+    failed: bool,
+}
+
+/// The sink stays reachable so the fast path can use its descriptor
+enum Writer {
+    Plain(Sink),
+    Encoded(EncodeWriter<Sink>),
+}
+
+/// The end of the line: stdout itself, the built-in pager in front of it, or an --output file
+pub enum Sink {
+    Stdout(StdoutLock<'static>),
+    Pager(Pager),
+    File(AtomicFile),
+}
+
+impl Sink {
+    /// `paging` is None to stream straight to stdout, or whether to page even short output
+    pub fn stdout(paging: Option<bool>) -> Sink {
+        match paging {
+            Some(always) => Sink::Pager(Pager::new(always)),
+            None => Sink::Stdout(io::stdout().lock()),
+        }
+    }
+}
+
+impl Output {
+    pub fn new(sink: Sink, encoding: Option<&'static Encoding>) -> Output {
+        let writer = match encoding {
+            Some(encoding) => Writer::Encoded(EncodeWriter::new(sink, encoding)),
+            None => Writer::Plain(sink),
+        };
+        Output {
+            writer,
+            failed: false,
+        }
+    }
+
+    /// Whether a write failed, which makes any error met since the output's
+    /// rather than that of the input being read
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Copy the rest of `input` to stdout or the output file with the fast path.
+    /// None if bytes written to the output do not reach the sink unchanged.
+    pub fn copy(&mut self, input: &mut Input) -> Option<io::Result<u64>> {
+        let target: &mut dyn Target = match &mut self.writer {
+            Writer::Plain(Sink::Stdout(stdout)) => stdout,
+            Writer::Plain(Sink::File(file)) => file,
+            _ => return None,
+        };
+        Some(fastcopy::copy(input, target).map_err(|e| match e {
+            CopyError::Read(e) => e,
+            CopyError::Write(e) => {
+                self.failed = true;
+                e
+            }
+        }))
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        let result = self.finish_writer();
+        self.track(result)
+    }
+
+    fn finish_writer(&mut self) -> io::Result<()> {
+        let sink = match &mut self.writer {
+            Writer::Plain(sink) => sink,
+            Writer::Encoded(encoded) => {
+                encoded.finish()?;
+                encoded.get_mut()
+            }
+        };
+        match sink {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::Pager(pager) => pager.finish(),
+            Sink::File(file) => file.finish(),
+        }
+    }
+
+    fn track<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        // write_all tries again after an interruption, so it does not count
+        if result
+            .as_ref()
+            .is_err_and(|e| e.kind() != io::ErrorKind::Interrupted)
+        {
+            self.failed = true;
+        }
+        result
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = match &mut self.writer {
+            Writer::Plain(sink) => sink.write(buf),
+            Writer::Encoded(encoded) => encoded.write(buf),
+        };
+        self.track(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = match &mut self.writer {
+            Writer::Plain(sink) => sink.flush(),
+            Writer::Encoded(encoded) => encoded.flush(),
+        };
+        self.track(result)
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Stdout(stdout) => stdout.write(buf),
+            Sink::Pager(pager) => pager.write(buf),
+            Sink::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::Pager(pager) => pager.flush(),
+            Sink::File(file) => file.flush(),
+        }
+    }
+}
+
+/// A file that is written under a temporary name next to `path` and only
+/// renamed over it by `finish`, so nothing reading `path` sees half of it.
+/// If `finish` is never reached the temporary file is removed again.
+pub struct AtomicFile {
+    writer: BufWriter<File>,
+    temp: Option<TempPath>,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> io::Result<AtomicFile> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        // Fails with a plainer message than creating the temporary file would
+        fs::metadata(dir)?;
+        let (file, temp) = NamedTempFile::new_in(dir)?.into_parts();
+        Ok(AtomicFile {
+            writer: BufWriter::new(file),
+            temp: Some(temp),
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        let Some(temp) = self.temp.take() else {
+            return Ok(());
+        };
+        let file = self.writer.get_ref();
+        // Temporary files are private; the result gets the mode it had, or a new file's usual one
+        match fs::metadata(&self.path) {
+            Ok(existing) => file.set_permissions(existing.permissions())?,
+            Err(_) => set_default_mode(file)?,
+        }
+        file.sync_all()?;
+        temp.persist(&self.path).map_err(|e| e.error)
+    }
+}
+
+#[cfg(unix)]
+fn set_default_mode(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // SAFETY: umask cannot fail, and the old mask is put straight back
+    let umask = unsafe {
+        let mask = libc::umask(0);
+        libc::umask(mask);
+        mask
+    };
+    file.set_permissions(fs::Permissions::from_mode(0o666 & !(umask as u32)))
+}
+
+#[cfg(not(unix))]
+fn set_default_mode(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for AtomicFile {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.writer.get_ref().as_raw_fd()
+    }
+}
@@ -0,0 +1,660 @@
+//! A small built-in pager, used instead of flooding a terminal with long output.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+
+use memchr::{memchr_iter, memmem};
+use thiserror::Error;
+
+/// Returned by writes once the user has quit the pager, to stop producing output nobody sees
+#[derive(Debug, Error)]
+#[error("the pager was closed")]
+pub struct PagerClosed;
+
+/// Shows what is written to it a screen at a time. Until the output fills a screen it goes
+/// straight to stdout; after that the pager takes over the terminal and writes wait while
+/// the user reads, so only what has been paged through so far is held in memory.
+pub struct Pager {
+    state: State,
+}
+
+enum State {
+    /// Nothing has overflowed the screen yet; `seen` is what was printed, at most a screen
+    Streaming { height: usize, seen: Vec<u8>, newlines: usize },
+    Paging(Box<Paging>),
+    /// The terminal could not be taken over, so everything streams
+    Plain,
+    /// The user quit
+    Closed,
+}
+
+impl Pager {
+    /// `always` pages from the first byte on, even output that would fit on one screen
+    pub fn new(always: bool) -> Pager {
+        let state = match terminal_rows() {
+            None => State::Plain,
+            Some(_) if always => Paging::start(Vec::new()),
+            Some(rows) => State::Streaming {
+                height: text_height(rows),
+                seen: Vec::new(),
+                newlines: 0,
+            },
+        };
+        Pager { state }
+    }
+
+    /// Show the rest of the output, and let the user read it until they quit
+    pub fn finish(&mut self) -> io::Result<()> {
+        match mem::replace(&mut self.state, State::Closed) {
+            State::Paging(mut paging) => {
+                paging.view.complete();
+                paging.serve()
+            }
+            State::Streaming { .. } | State::Plain => io::stdout().flush(),
+            State::Closed => Ok(()),
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.state {
+            State::Streaming { height, seen, newlines } => {
+                // Bytes after the last line that fits mean the output needs more than a screen
+                let last_line_end = match height.checked_sub(*newlines + 1) {
+                    Some(n) => memchr_iter(b'\n', buf).nth(n).map(|i| i + 1),
+                    None => Some(0),
+                };
+                let Some(end) = last_line_end.filter(|&end| end < buf.len()) else {
+                    *newlines += memchr_iter(b'\n', buf).count();
+                    seen.extend_from_slice(buf);
+                    io::stdout().write_all(buf)?;
+                    return Ok(buf.len());
+                };
+                io::stdout().write_all(&buf[..end])?;
+                seen.extend_from_slice(&buf[..end]);
+                self.state = Paging::start(mem::take(seen));
+                self.write_all(&buf[end..])?;
+                Ok(buf.len())
+            }
+            State::Paging(paging) => {
+                paging.push(buf);
+                if let Err(e) = paging.serve() {
+                    self.state = State::Closed;
+                    return Err(e);
+                }
+                Ok(buf.len())
+            }
+            State::Plain => io::stdout().write(buf),
+            State::Closed => Err(io::Error::other(PagerClosed)),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Streaming { .. } | State::Plain => io::stdout().flush(),
+            State::Paging(_) | State::Closed => Ok(()),
+        }
+    }
+}
+
+impl Drop for Pager {
+    /// On an early return the user still gets to read what was produced before it
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            let _ = self.finish();
+        }
+    }
+}
+
+/// Whether `err` only says that the user quit the pager
+pub fn is_closed(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>()
+        .and_then(|e| e.get_ref())
+        .is_some_and(|e| e.is::<PagerClosed>())
+}
+
+/// Rows left for text once the status line is drawn
+fn text_height(rows: usize) -> usize {
+    rows.saturating_sub(1).max(1)
+}
+
+/// The pager in charge of the terminal
+struct Paging {
+    view: View,
+    tty: Tty,
+    /// Bytes of a line whose end has not been written yet
+    partial: Vec<u8>,
+}
+
+impl Paging {
+    /// Take over the terminal showing `seen`, or fall back to streaming if it cannot be had
+    fn start(seen: Vec<u8>) -> State {
+        let Ok(tty) = Tty::open() else {
+            return State::Plain;
+        };
+        let mut paging = Paging {
+            view: View::new(Vec::new(), terminal_rows().unwrap_or(1)),
+            tty,
+            partial: Vec::new(),
+        };
+        // Alternate screen, hidden cursor, long lines clipped rather than wrapped
+        if io::stdout().write_all(b"\x1b[?1049h\x1b[?25l\x1b[?7l").is_err() {
+            return State::Plain;
+        }
+        paging.push(&seen);
+        State::Paging(Box::new(paging))
+    }
+
+    fn push(&mut self, mut buf: &[u8]) {
+        while let Some(i) = memchr::memchr(b'\n', buf) {
+            self.partial.extend_from_slice(&buf[..i]);
+            let mut line = mem::take(&mut self.partial);
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            self.view.push(line);
+            buf = &buf[i + 1..];
+        }
+        self.partial.extend_from_slice(buf);
+    }
+
+    /// Show the screen and handle keys for as long as no more output is needed.
+    /// Fails with `PagerClosed` when the user quits before the end of the output
+    fn serve(&mut self) -> io::Result<()> {
+        if self.view.complete && !self.partial.is_empty() {
+            let line = mem::take(&mut self.partial);
+            self.view.push(line);
+        }
+        let mut screen = io::stdout().lock();
+        loop {
+            if self.view.wants_more() {
+                // Redraw only while the screen is still filling, not for every line after it
+                if self.view.lines.len() <= self.view.top + self.view.height {
+                    self.view.render(&mut screen, self.view.status().as_bytes())?;
+                }
+                return Ok(());
+            }
+            if let Some(rows) = terminal_rows() {
+                self.view.resize(rows);
+            }
+            self.view.render(&mut screen, self.view.status().as_bytes())?;
+
+            let quit = match self.tty.key()? {
+                None => true,
+                Some(Key::Search) => {
+                    if let Some(pattern) = self.view.prompt(&mut self.tty, &mut screen)? {
+                        self.view.search(pattern);
+                    }
+                    false
+                }
+                Some(key) => !self.view.press(key),
+            };
+            if quit {
+                return if self.view.complete {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(PagerClosed))
+                };
+            }
+        }
+    }
+}
+
+impl Drop for Paging {
+    fn drop(&mut self) {
+        let mut screen = io::stdout().lock();
+        let _ = screen.write_all(b"\x1b[?7h\x1b[?25h\x1b[?1049l");
+        let _ = screen.flush();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Quit,
+    Down(usize),
+    Up(usize),
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    Search,
+    Next,
+    Previous,
+    Other,
+}
+
+/// Decode the first key in `input`, returning it and how many bytes it took
+fn parse_key(input: &[u8]) -> (Key, usize) {
+    let escapes: [(&[u8], Key); 10] = [
+        (b"\x1b[A", Key::Up(1)),
+        (b"\x1b[B", Key::Down(1)),
+        (b"\x1b[5~", Key::PageUp),
+        (b"\x1b[6~", Key::PageDown),
+        (b"\x1b[H", Key::Top),
+        (b"\x1b[1~", Key::Top),
+        (b"\x1b[F", Key::Bottom),
+        (b"\x1b[4~", Key::Bottom),
+        (b"\x1bOA", Key::Up(1)),
+        (b"\x1bOB", Key::Down(1)),
+    ];
+    for (sequence, key) in escapes {
+        if input.starts_with(sequence) {
+            return (key, sequence.len());
+        }
+    }
+    let key = match input.first() {
+        None => return (Key::Other, 0),
+        Some(b'q' | b'Q' | 0x03) => Key::Quit,
+        Some(b'j' | b'e' | b'\r' | b'\n') => Key::Down(1),
+        Some(b'k' | b'y') => Key::Up(1),
+        Some(b' ' | b'f' | 0x06) => Key::PageDown,
+        Some(b'b' | 0x02) => Key::PageUp,
+        Some(b'd' | 0x04) => Key::HalfPageDown,
+        Some(b'u' | 0x15) => Key::HalfPageUp,
+        Some(b'g' | b'<') => Key::Top,
+        Some(b'G' | b'>') => Key::Bottom,
+        Some(b'/') => Key::Search,
+        Some(b'n') => Key::Next,
+        Some(b'N') => Key::Previous,
+        Some(_) => Key::Other,
+    };
+    (key, 1)
+}
+
+/// What is on screen: the lines, which one is at the top, and the status line
+struct View {
+    lines: Vec<Vec<u8>>,
+    /// All the output is in `lines`
+    complete: bool,
+    top: usize,
+    /// Rows left for text once the status line is drawn
+    height: usize,
+    /// Jump to the end once it has been read
+    to_end: bool,
+    pattern: Option<Vec<u8>>,
+    /// Line of the last match, where n and N carry on from
+    matched: Option<usize>,
+    /// A forward search found nothing yet, and goes on in the lines still to come
+    searching: bool,
+    message: Option<String>,
+}
+
+impl View {
+    fn new(lines: Vec<Vec<u8>>, rows: usize) -> View {
+        View {
+            lines,
+            complete: false,
+            top: 0,
+            height: text_height(rows),
+            to_end: false,
+            pattern: None,
+            matched: None,
+            searching: false,
+            message: None,
+        }
+    }
+
+    fn push(&mut self, line: Vec<u8>) {
+        self.lines.push(line);
+        if self.searching {
+            let found = self.pattern.as_ref().is_some_and(|pattern| {
+                memmem::find(&self.lines[self.lines.len() - 1], pattern).is_some()
+            });
+            if found {
+                self.searching = false;
+                self.matched = Some(self.lines.len() - 1);
+                self.scroll_to(self.lines.len() - 1);
+            }
+        }
+    }
+
+    /// No more lines are coming
+    fn complete(&mut self) {
+        self.complete = true;
+        if mem::take(&mut self.searching) {
+            self.message = Some("Pattern not found".to_string());
+        }
+        if mem::take(&mut self.to_end) {
+            self.top = self.last_top();
+        }
+        self.scroll_to(self.top);
+    }
+
+    /// Whether the screen cannot be drawn, or the last key done, without more lines
+    fn wants_more(&self) -> bool {
+        !self.complete && (self.to_end || self.searching || self.lines.len() < self.top + self.height)
+    }
+
+    fn resize(&mut self, rows: usize) {
+        self.height = text_height(rows);
+        self.scroll_to(self.top);
+    }
+
+    fn last_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    /// Until the end is known, scrolling past what has arrived so far asks for more
+    fn scroll_to(&mut self, top: usize) {
+        self.top = if self.complete { top.min(self.last_top()) } else { top.min(self.lines.len()) };
+    }
+
+    /// Apply a movement key; false means quit
+    fn press(&mut self, key: Key) -> bool {
+        self.message = None;
+        let half = (self.height / 2).max(1);
+        match key {
+            Key::Quit => return false,
+            Key::Down(n) => self.scroll_to(self.top + n),
+            Key::Up(n) => self.scroll_to(self.top.saturating_sub(n)),
+            Key::PageDown => self.scroll_to(self.top + self.height),
+            Key::PageUp => self.scroll_to(self.top.saturating_sub(self.height)),
+            Key::HalfPageDown => self.scroll_to(self.top + half),
+            Key::HalfPageUp => self.scroll_to(self.top.saturating_sub(half)),
+            Key::Top => self.top = 0,
+            Key::Bottom if self.complete => self.top = self.last_top(),
+            Key::Bottom => self.to_end = true,
+            Key::Next => self.find(true),
+            Key::Previous => self.find(false),
+            Key::Search | Key::Other => {}
+        }
+        true
+    }
+
+    /// Move the next (or previous) line containing the pattern to the top
+    fn find(&mut self, forward: bool) {
+        let Some(pattern) = &self.pattern else {
+            self.message = Some("No previous search".to_string());
+            return;
+        };
+        let finder = memmem::Finder::new(pattern);
+        let from = self.matched.unwrap_or(self.top);
+        let found = if forward {
+            (from + 1..self.lines.len()).find(|&i| finder.find(&self.lines[i]).is_some())
+        } else {
+            (0..from)
+                .rev()
+                .find(|&i| finder.find(&self.lines[i]).is_some())
+        };
+        match found {
+            // A match near the end still shows a full screen, so it may sit below the top
+            Some(line) => {
+                self.matched = Some(line);
+                self.scroll_to(line);
+            }
+            None if forward && !self.complete => self.searching = true,
+            None => self.message = Some("Pattern not found".to_string()),
+        }
+    }
+
+    fn search(&mut self, pattern: Vec<u8>) {
+        if !pattern.is_empty() {
+            self.pattern = Some(pattern);
+        }
+        self.matched = None;
+        self.find(true);
+    }
+
+    fn status(&self) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        let last = (self.top + self.height).min(self.lines.len());
+        if !self.complete {
+            format!("lines {}-{}", self.top + 1, last)
+        } else if last == self.lines.len() {
+            "(END)".to_string()
+        } else {
+            format!("lines {}-{}/{}", self.top + 1, last, self.lines.len())
+        }
+    }
+
+    fn render(&self, screen: &mut impl Write, status: &[u8]) -> io::Result<()> {
+        let mut frame = b"\x1b[H".to_vec();
+        for row in 0..self.height {
+            frame.extend_from_slice(b"\x1b[2K");
+            match self.lines.get(self.top + row) {
+                Some(line) => frame.extend_from_slice(line),
+                None if self.complete => frame.push(b'~'),
+                None => {}
+            }
+            frame.extend_from_slice(b"\x1b[0m\r\n");
+        }
+        frame.extend_from_slice(b"\x1b[2K\x1b[7m");
+        frame.extend_from_slice(status);
+        frame.extend_from_slice(b"\x1b[0m");
+        screen.write_all(&frame)?;
+        screen.flush()
+    }
+
+    /// Read a search pattern on the status line; None if it was cancelled
+    fn prompt(&self, tty: &mut Tty, screen: &mut impl Write) -> io::Result<Option<Vec<u8>>> {
+        let mut pattern = Vec::new();
+        loop {
+            let mut status = b"/".to_vec();
+            status.extend_from_slice(&pattern);
+            self.render(screen, &status)?;
+            let Some(byte) = tty.byte()? else {
+                return Ok(None);
+            };
+            match byte {
+                b'\r' | b'\n' => return Ok(Some(pattern)),
+                0x1b | 0x03 => return Ok(None),
+                0x7f | 0x08 => {
+                    if pattern.pop().is_none() {
+                        return Ok(None);
+                    }
+                }
+                b => pattern.push(b),
+            }
+        }
+    }
+}
+
+/// The controlling terminal, switched to unbuffered input without echo until dropped
+struct Tty {
+    file: File,
+    /// Bytes read but not yet taken as keys
+    pending: Vec<u8>,
+    #[cfg(unix)]
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl Tty {
+    fn open() -> io::Result<Tty> {
+        use std::os::unix::io::AsRawFd;
+
+        // Keys come from the terminal itself, since stdin may be what is being paged
+        let file = File::open("/dev/tty")?;
+        let fd = file.as_raw_fd();
+        // SAFETY: termios is a plain C struct for which all zeroes is a valid value
+        let mut saved = unsafe { mem::zeroed::<libc::termios>() };
+        // SAFETY: fd is open for as long as `file` lives, and `saved` is a valid termios to fill
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: fd is still open, and `raw` is an initialised termios read back from it
+        if unsafe { libc::tcsetattr(fd, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Tty {
+            file,
+            pending: Vec::new(),
+            saved,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Tty {
+    fn drop(&mut self) {
+        use std::os::unix::io::AsRawFd;
+
+        // SAFETY: the descriptor is owned by `self.file`, and `saved` came from tcgetattr on it
+        unsafe { libc::tcsetattr(self.file.as_raw_fd(), libc::TCSAFLUSH, &self.saved) };
+    }
+}
+
+#[cfg(not(unix))]
+impl Tty {
+    fn open() -> io::Result<Tty> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "no terminal control",
+        ))
+    }
+}
+
+impl Tty {
+    /// Make sure there is pending input; false at end of input
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.pending.is_empty() {
+            let mut buf = [0; 32];
+            let read = self.file.read(&mut buf)?;
+            self.pending.extend_from_slice(&buf[..read]);
+        }
+        Ok(!self.pending.is_empty())
+    }
+
+    fn key(&mut self) -> io::Result<Option<Key>> {
+        if !self.fill()? {
+            return Ok(None);
+        }
+        let (key, used) = parse_key(&self.pending);
+        self.pending.drain(..used);
+        Ok(Some(key))
+    }
+
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        if !self.fill()? {
+            return Ok(None);
+        }
+        Ok(Some(self.pending.remove(0)))
+    }
+}
+
+/// Height of the terminal on stdout, if it is one
+#[cfg(unix)]
+fn terminal_rows() -> Option<usize> {
+    // SAFETY: winsize is a plain C struct for which all zeroes is a valid value
+    let mut size = unsafe { mem::zeroed::<libc::winsize>() };
+    // SAFETY: TIOCGWINSZ only writes a winsize through the pointer, which points at one;
+    // on a descriptor that is not a terminal it fails without touching it
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_row > 0).then_some(size.ws_row as usize)
+}
+
+#[cfg(not(unix))]
+fn terminal_rows() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete_view(text: &[u8], rows: usize) -> View {
+        let lines = text.strip_suffix(b"\n").unwrap_or(text).split(|&b| b == b'\n').map(<[u8]>::to_vec);
+        let mut view = View::new(lines.collect(), rows);
+        view.complete();
+        view
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key(b"q"), (Key::Quit, 1));
+        assert_eq!(parse_key(b"\x1b[6~j"), (Key::PageDown, 4));
+        assert_eq!(parse_key(b"\x1b[A"), (Key::Up(1), 3));
+        assert_eq!(parse_key(b"/"), (Key::Search, 1));
+        assert_eq!(parse_key(b"x"), (Key::Other, 1));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let text = b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let mut view = complete_view(text, 4);
+        assert_eq!(view.lines.len(), 10);
+        assert_eq!(view.status(), "lines 1-3/10");
+        view.press(Key::PageDown);
+        assert_eq!(view.top, 3);
+        view.press(Key::Bottom);
+        assert_eq!(view.top, 7);
+        assert_eq!(view.status(), "(END)");
+        view.press(Key::Down(5));
+        assert_eq!(view.top, 7);
+        view.press(Key::Up(10));
+        assert_eq!(view.top, 0);
+        assert!(!view.press(Key::Quit));
+    }
+
+    #[test]
+    fn test_search() {
+        let text = b"apple\nbanana\ncherry\nbanana split\ndate\nfig\n";
+        let mut view = complete_view(text, 3);
+        view.search(b"banana".to_vec());
+        assert_eq!(view.top, 1);
+        view.press(Key::Next);
+        assert_eq!(view.top, 3);
+        view.press(Key::Next);
+        assert_eq!(view.top, 3);
+        assert_eq!(view.status(), "Pattern not found");
+        view.press(Key::Previous);
+        assert_eq!(view.top, 1);
+    }
+
+    #[test]
+    fn test_streaming_view() {
+        let mut view = View::new(Vec::new(), 4);
+        assert!(view.wants_more());
+        for line in [b"1", b"2", b"3"] {
+            view.push(line.to_vec());
+        }
+        assert!(!view.wants_more());
+        assert_eq!(view.status(), "lines 1-3");
+
+        // Scrolling past what has arrived asks for the next lines instead of stopping
+        view.press(Key::Down(1));
+        assert_eq!(view.top, 1);
+        assert!(view.wants_more());
+        view.push(b"4".to_vec());
+        assert!(!view.wants_more());
+
+        view.press(Key::Bottom);
+        assert!(view.wants_more());
+        view.push(b"5".to_vec());
+        view.push(b"6".to_vec());
+        view.complete();
+        assert_eq!(view.top, 3);
+        assert_eq!(view.status(), "(END)");
+    }
+
+    #[test]
+    fn test_search_ahead() {
+        let mut view = View::new(vec![b"a".to_vec(), b"b".to_vec()], 3);
+        view.search(b"needle".to_vec());
+        assert!(view.wants_more());
+        view.push(b"c".to_vec());
+        view.push(b"a needle".to_vec());
+        assert_eq!(view.matched, Some(3));
+        assert_eq!(view.top, 3);
+        // The rest of the screen below the match is still to come
+        assert!(view.wants_more());
+        view.push(b"d".to_vec());
+        assert!(!view.wants_more());
+
+        view.press(Key::Next);
+        assert!(view.wants_more());
+        view.complete();
+        assert_eq!(view.status(), "Pattern not found");
+        assert_eq!(view.top, 3);
+    }
+}
@@ -0,0 +1,228 @@
+//! Reading records from the last one to the first, like `tac`.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use memchr::memmem;
+use regex::bytes::Regex;
+
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// What ends a record; the separator stays attached to the record it ends.
+/// A literal separator must not be empty, and empty regex matches are skipped.
+#[derive(Debug, Clone)]
+pub enum Separator {
+    Literal(Vec<u8>),
+    Regex(Regex),
+}
+
+impl Separator {
+    /// Whether `record` is nothing but its separator, the way an empty line is only "\n"
+    pub fn is_blank(&self, record: &[u8]) -> bool {
+        match self {
+            Separator::Literal(separator) => record == separator.as_slice(),
+            Separator::Regex(regex) => regex
+                .find(record)
+                .is_some_and(|m| m.start() == 0 && m.end() == record.len()),
+        }
+    }
+}
+
+/// Records of `file` from offset `start` to its end, last record first
+pub fn records<'a>(
+    file: &'a File,
+    start: u64,
+    separator: &'a Separator,
+) -> io::Result<Box<dyn Iterator<Item = io::Result<Vec<u8>>> + 'a>> {
+    match separator {
+        Separator::Literal(separator) => {
+            let end = file.metadata()?.len();
+            // Whether the last record has its separator decides where the search for the one before starts
+            let mut tail = vec![0; separator.len().min((end - start) as usize)];
+            let mut file = file;
+            file.seek(SeekFrom::Start(end - tail.len() as u64))?;
+            file.read_exact(&mut tail)?;
+            let terminated = tail == *separator;
+            Ok(Box::new(BlockRecords { file, start, pos: end, buf: Vec::new(), carry: Vec::new(), separator, terminated }))
+        }
+        Separator::Regex(regex) => {
+            // A match may span any number of blocks, so regexes work on the whole input:
+            // memory use is the size of the file, plus one offset per record
+            let mut data = Vec::new();
+            let mut file = file;
+            file.seek(SeekFrom::Start(start))?;
+            file.read_to_end(&mut data)?;
+
+            if data.is_empty() {
+                return Ok(Box::new(std::iter::empty()));
+            }
+
+            let mut bounds = vec![0];
+            bounds.extend(
+                regex
+                    .find_iter(&data)
+                    .filter(|m| !m.is_empty() && m.end() < data.len())
+                    .map(|m| m.end()),
+            );
+            bounds.push(data.len());
+            Ok(Box::new(RegexRecords { data, bounds }))
+        }
+    }
+}
+
+/// Records of an input held in memory, cut at `bounds` and copied out one at a time
+struct RegexRecords {
+    data: Vec<u8>,
+    /// Start of every record, then the end of the last one
+    bounds: Vec<usize>,
+}
+
+impl Iterator for RegexRecords {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.bounds.pop()?;
+        let start = *self.bounds.last()?;
+        Some(Ok(self.data[start..end].to_vec()))
+    }
+}
+
+/// Reads a file backwards one block at a time, so memory use is bounded by the longest record.
+/// A record longer than a block is kept as the blocks it was read in, and joined once when
+/// complete, so no byte is copied or searched more than a fixed number of times.
+struct BlockRecords<'a> {
+    file: &'a File,
+    start: u64,
+    /// File offset of `buf[0]`
+    pos: u64,
+    /// Data from `pos` up to the start of `carry`, not yet returned
+    buf: Vec<u8>,
+    /// The rest of the record being read, in blocks that hold no separator, last block first
+    carry: Vec<Vec<u8>>,
+    separator: &'a [u8],
+    /// Whether the data still to be returned ends with a separator
+    terminated: bool,
+}
+
+impl BlockRecords<'_> {
+    /// Read the block before `buf`, moving what `buf` held into `carry`. The first bytes of the
+    /// old `buf` stay behind, so a separator straddling the two blocks is still found in `buf`
+    fn read_block(&mut self) -> io::Result<()> {
+        let size = BLOCK_SIZE.min(self.pos - self.start);
+        self.pos -= size;
+        let mut block = vec![0; size as usize];
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        self.file.read_exact(&mut block)?;
+
+        let overlap = (self.separator.len() - 1).min(self.buf.len());
+        block.extend_from_slice(&self.buf[..overlap]);
+        let rest = self.buf.split_off(overlap);
+        if !rest.is_empty() {
+            self.carry.push(rest);
+        }
+        self.buf = block;
+        Ok(())
+    }
+
+    /// Everything from `buf[from..]` on, which ends the records still to be returned
+    fn take_record(&mut self, from: usize) -> Vec<u8> {
+        let mut record = self.buf.split_off(from);
+        for block in self.carry.drain(..).rev() {
+            record.extend_from_slice(&block);
+        }
+        record
+    }
+}
+
+impl Iterator for BlockRecords<'_> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // The separator ending the last record is part of it, and so are its bytes to a match
+            // overlapping it, so that a run of separators is cut into whole ones
+            let pending = self.buf.len() + self.carry.iter().map(Vec::len).sum::<usize>();
+            let own = if self.terminated { self.separator.len() } else { 1 };
+            let searched = &self.buf[..self.buf.len().min(pending.saturating_sub(own))];
+
+            match memmem::rfind(searched, self.separator) {
+                Some(i) => {
+                    self.terminated = true;
+                    return Some(Ok(self.take_record(i + self.separator.len())));
+                }
+                None if self.pos == self.start && pending == 0 => return None,
+                None if self.pos == self.start => return Some(Ok(self.take_record(0))),
+                None => {
+                    if let Err(e) = self.read_block() {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{records, Separator, BLOCK_SIZE};
+
+    fn reversed(data: &[u8], separator: &Separator) -> Vec<Vec<u8>> {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(data).unwrap();
+        records(&file, 0, separator).unwrap().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_long_records() {
+        let block = BLOCK_SIZE as usize;
+        let long = vec![b'x'; 3 * block + 7];
+        let data = [&b"a\n"[..], &long, b"\nb\n"].concat();
+        let expected = vec![b"b\n".to_vec(), [&long[..], b"\n"].concat(), b"a\n".to_vec()];
+        assert_eq!(reversed(&data, &Separator::Literal(b"\n".to_vec())), expected);
+    }
+
+    #[test]
+    fn test_separator_across_blocks() {
+        let block = BLOCK_SIZE as usize;
+        for offset in [block - 2, block - 1, block, block + 1] {
+            // Put "--" so it starts just before, on or after a block boundary counted from the end
+            let tail = vec![b'y'; offset];
+            let data = [&b"x--"[..], &tail].concat();
+            let head = data.len() - offset;
+            let expected = vec![data[head..].to_vec(), data[..head].to_vec()];
+            assert_eq!(reversed(&data, &Separator::Literal(b"--".to_vec())), expected, "{offset}");
+        }
+    }
+
+    #[test]
+    fn test_separator_runs() {
+        let separator = Separator::Literal(b"--".to_vec());
+        let records = |data: &[u8]| reversed(data, &separator);
+        assert_eq!(records(b"a------b--"), [&b"b--"[..], b"--", b"--", b"a--"]);
+        // Like tac, the odd dash stays with the first record
+        assert_eq!(records(b"a-----"), [&b"--"[..], b"a---"]);
+        assert_eq!(records(b"----x"), [&b"x"[..], b"--", b"--"]);
+        assert_eq!(records(b"-"), [b"-"]);
+    }
+
+    #[test]
+    fn test_regex() {
+        let separator = Separator::Regex(regex::bytes::Regex::new("[,;]").unwrap());
+        assert_eq!(reversed(b"a,b;c", &separator), vec![b"c".to_vec(), b"b;".to_vec(), b"a,".to_vec()]);
+        assert!(reversed(b"", &separator).is_empty());
+    }
+
+    #[test]
+    fn test_is_blank() {
+        let literal = Separator::Literal(b"--".to_vec());
+        assert!(literal.is_blank(b"--"));
+        assert!(!literal.is_blank(b"\n"));
+        assert!(!literal.is_blank(b"a--"));
+        let regex = Separator::Regex(regex::bytes::Regex::new("[,;]+").unwrap());
+        assert!(regex.is_blank(b",;"));
+        assert!(!regex.is_blank(b"a,"));
+        assert!(!regex.is_blank(b""));
+    }
+}
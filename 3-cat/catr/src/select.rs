@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RangeError {
+    #[error("invalid line number '{0}'")]
+    BadNumber(String),
+    #[error("line numbers start at 1")]
+    Zero,
+}
+
+/// One `START:END` range of 1-based line numbers, both ends inclusive.
+/// Negative numbers count from the end, so -1 is the last line.
+#[derive(Debug, Clone, PartialEq)]
+struct Range {
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl Range {
+    fn contains(&self, line: u64, total: Option<u64>) -> bool {
+        let start = self.start.map_or(Some(1), |n| resolve(n, total));
+        let end = self.end.map_or(Some(u64::MAX), |n| resolve(n, total));
+        matches!((start, end), (Some(start), Some(end)) if start <= line && line <= end)
+    }
+}
+
+/// Turn a possibly negative line number into an absolute one, where 0 means
+/// "before the first line"; `None` if it counts from an unknown end
+fn resolve(n: i64, total: Option<u64>) -> Option<u64> {
+    if n > 0 {
+        Some(n as u64)
+    } else {
+        Some((total? + 1).saturating_sub(n.unsigned_abs()))
+    }
+}
+
+/// The value of `--lines`: a comma-separated list of ranges such as `10:20,100:,:-5`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineRanges(Vec<Range>);
+
+impl LineRanges {
+    /// Whether some bound counts from the end, so the number of lines must be known up front
+    pub fn needs_total(&self) -> bool {
+        self.0
+            .iter()
+            .any(|range| range.start.is_some_and(|n| n < 0) || range.end.is_some_and(|n| n < 0))
+    }
+
+    pub fn contains(&self, line: u64, total: Option<u64>) -> bool {
+        self.0.iter().any(|range| range.contains(line, total))
+    }
+
+    /// The last line any range can select, if the ranges are bounded
+    pub fn last(&self, total: Option<u64>) -> Option<u64> {
+        self.0
+            .iter()
+            .map(|range| range.end.and_then(|n| resolve(n, total)))
+            .try_fold(0, |last, end| end.map(|end| last.max(end)))
+    }
+}
+
+impl FromStr for LineRanges {
+    type Err = RangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bound = |s: &str| -> Result<Option<i64>, RangeError> {
+            match s {
+                "" => Ok(None),
+                _ => match s.parse() {
+                    Ok(0) => Err(RangeError::Zero),
+                    Ok(n) => Ok(Some(n)),
+                    Err(_) => Err(RangeError::BadNumber(s.to_string())),
+                },
+            }
+        };
+
+        s.split(',')
+            .map(|part| match part.split_once(':') {
+                Some((start, end)) => Ok(Range { start: bound(start)?, end: bound(end)? }),
+                None => {
+                    let line = bound(part)?.ok_or_else(|| RangeError::BadNumber(part.to_string()))?;
+                    Ok(Range { start: Some(line), end: Some(line) })
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(LineRanges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(ranges: &str, total: u64) -> Vec<u64> {
+        let ranges: LineRanges = ranges.parse().unwrap();
+        (1..=total).filter(|&line| ranges.contains(line, Some(total))).collect()
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(selected("3:5", 10), vec![3, 4, 5]);
+        assert_eq!(selected("8:", 10), vec![8, 9, 10]);
+        assert_eq!(selected(":-8", 10), vec![1, 2, 3]);
+        assert_eq!(selected("-2:", 10), vec![9, 10]);
+        assert_eq!(selected("1,4:5,-1", 10), vec![1, 4, 5, 10]);
+        assert_eq!(selected("-20:2", 10), vec![1, 2]);
+        assert_eq!(selected(":-20", 10), vec![]);
+        assert_eq!(selected("5:3", 10), vec![]);
+    }
+
+    #[test]
+    fn test_last() {
+        let last = |ranges: &str, total| ranges.parse::<LineRanges>().unwrap().last(total);
+        assert_eq!(last("3:5,2", None), Some(5));
+        assert_eq!(last("3:", None), None);
+        assert_eq!(last(":-2", Some(10)), Some(9));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("0:3".parse::<LineRanges>(), Err(RangeError::Zero));
+        assert_eq!("a:3".parse::<LineRanges>(), Err(RangeError::BadNumber("a".to_string())));
+        assert_eq!("".parse::<LineRanges>(), Err(RangeError::BadNumber("".to_string())));
+    }
+}
@@ -0,0 +1,72 @@
+use std::io::{self, Write};
+
+use crate::eol::Eol;
+
+/// Which invisible characters to reveal in the output
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Visualizer {
+    pub nonprinting: bool,
+    pub ends: bool,
+    pub tabs: bool,
+    /// Line ending to write in place of `\n`
+    pub eol: Option<Eol>,
+}
+
+impl Visualizer {
+    pub fn is_identity(&self) -> bool {
+        !(self.nonprinting || self.ends || self.tabs || self.eol.is_some())
+    }
+
+    /// Write one line (including its trailing `\n`, if any) using caret and M- notation
+    pub fn write(&self, out: &mut impl Write, line: &[u8]) -> io::Result<()> {
+        if self.is_identity() {
+            return out.write_all(line);
+        }
+
+        let (mut body, newline) = match line.strip_suffix(b"\n") {
+            Some(body) => (body, true),
+            None => (line, false),
+        };
+
+        // Like GNU cat, -E alone still reveals the CR of a CRLF ending as ^M$
+        let crlf = self.ends && newline && body.ends_with(b"\r");
+        if crlf {
+            body = &body[..body.len() - 1];
+        }
+
+        for &c in body {
+            match c {
+                b'\t' if self.tabs => out.write_all(b"^I")?,
+                b'\t' => out.write_all(b"\t")?,
+                _ if self.nonprinting => write_nonprinting(out, c)?,
+                _ => out.write_all(&[c])?,
+            }
+        }
+
+        if crlf {
+            out.write_all(b"^M")?;
+        }
+        if self.ends && newline {
+            out.write_all(b"$")?;
+        }
+        if newline {
+            out.write_all(self.eol.map_or(b"\n", Eol::terminator))?;
+        }
+        Ok(())
+    }
+}
+
+fn write_nonprinting(out: &mut impl Write, c: u8) -> io::Result<()> {
+    let c = if c >= 128 {
+        out.write_all(b"M-")?;
+        c - 128
+    } else {
+        c
+    };
+
+    match c {
+        0..=31 => out.write_all(&[b'^', c + 64]),
+        127 => out.write_all(b"^?"),
+        _ => out.write_all(&[c]),
+    }
+}
@@ -0,0 +1,74 @@
+//! Expanding directories into the files under them, for -r.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+
+/// Which files under a directory to keep. A pattern containing `/` is matched
+/// against the path below the directory, any other against the file name alone.
+#[derive(Debug)]
+pub struct Filter<'a> {
+    pub include: &'a [Pattern],
+    pub exclude: &'a [Pattern],
+}
+
+fn matches(pattern: &Pattern, relative: &str, name: &str) -> bool {
+    if pattern.as_str().contains('/') {
+        // * and ? stay within one directory, as in a shell
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        pattern.matches_with(relative, options)
+    } else {
+        pattern.matches(name)
+    }
+}
+
+impl Filter<'_> {
+    fn excludes(&self, relative: &str, name: &str) -> bool {
+        self.exclude.iter().any(|p| matches(p, relative, name))
+    }
+
+    fn includes(&self, relative: &str, name: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|p| matches(p, relative, name))
+    }
+}
+
+pub fn parse_glob(pattern: &str) -> Result<Pattern, String> {
+    Pattern::new(pattern).map_err(|e| format!("invalid glob '{}': {}", pattern, e))
+}
+
+/// Append the files under `dir` to `files`, walking subdirectories depth first
+/// with entries sorted by name. Symbolic links to directories are not followed,
+/// so a link back up the tree cannot loop.
+pub fn walk(dir: &str, filter: &Filter, files: &mut Vec<String>) -> io::Result<()> {
+    walk_below(Path::new(dir), "", filter, files)
+}
+
+fn walk_below(
+    dir: &Path,
+    prefix: &str,
+    filter: &Filter,
+    files: &mut Vec<String>,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let relative = format!("{}{}", prefix, name);
+        if filter.excludes(&relative, &name) {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk_below(&path, &format!("{}/", relative), filter, files)?;
+        } else if path.is_file() && filter.includes(&relative, &name) {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
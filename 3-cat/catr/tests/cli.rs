@@ -4,12 +4,35 @@ use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 const PRG: &str = "catr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const LATIN1: &str = "tests/inputs/latin1.txt";
+const CONTROL: &str = "tests/inputs/control.txt";
+const BLANKS: &str = "tests/inputs/blanks.txt";
+const FOX_GZ: &str = "tests/inputs/fox.txt.gz";
+const SPIDERS_XZ: &str = "tests/inputs/spiders.txt.xz";
+const SPIDERS_ZST: &str = "tests/inputs/spiders.txt.zst";
+const BUSTLE_BZ2: &str = "tests/inputs/the-bustle.txt.bz2";
+const MULTI_GZ: &str = "tests/inputs/multi.txt.gz";
+const TRUNCATED_GZ: &str = "tests/inputs/truncated.txt.gz";
+const DASHES: &str = "tests/inputs/dashes.txt";
+const MIXED_EOL: &str = "tests/inputs/mixed-eol.txt";
+const UTF16LE: &str = "tests/inputs/utf16le-bom.csv";
+const UTF16BE: &str = "tests/inputs/utf16be-bom.csv";
+const BOM_BIN: &str = "tests/inputs/bom.bin";
+const SHIFT_JIS: &str = "tests/inputs/shift_jis.txt";
+const BUNDLE_TGZ: &str = "tests/inputs/bundle.tar.gz";
+const BUNDLE_ZIP: &str = "tests/inputs/bundle.zip";
+const TREE: &str = "tests/inputs/tree";
 
 // --------------------------------------------------
 #[test]
@@ -46,11 +69,63 @@ fn skips_bad_file() -> Result<()> {
     Command::cargo_bin(PRG)?
         .arg(&bad)
         .assert()
-        .success()
+        .failure()
+        .code(1)
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn continues_after_bad_file() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    Command::cargo_bin(PRG)?
+        .args([FOX, &bad, SPIDERS, BUSTLE])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(expected)
+        .stderr(predicate::str::contains(format!("{bad}: ")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn counts_bad_files() -> Result<()> {
+    let (bad1, bad2) = (gen_bad_file(), gen_bad_file());
+    Command::cargo_bin(PRG)?
+        .args([&bad1, FOX, &bad2])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(format!(
+            "catr: {bad1}: No such file or directory (os error 2)\n\
+             catr: {bad2}: No such file or directory (os error 2)\n"
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn continues_after_corrupt_input() -> Result<()> {
+    let fox = fs::read_to_string(FOX)?;
+    // Once with the fast path, once through the line-by-line loop
+    for (flags, first) in [(&["-z"][..], "The bustle"), (&["-z", "-n"], "     1\tThe bustle")] {
+        let output = Command::cargo_bin(PRG)?
+            .args(flags)
+            .args([TRUNCATED_GZ, FOX])
+            .output()?;
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.starts_with(first), "{}", stdout);
+        assert!(stdout.ends_with(fox.as_str()), "{}", stdout);
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(stderr.contains(&format!("{TRUNCATED_GZ}: incomplete deflate stream")), "{}", stderr);
+    }
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> Result<()> {
     let expected = fs::read_to_string(expected_file)?;
@@ -63,6 +138,16 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_bytes(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run_stdin(
     input_file: &str,
@@ -190,6 +275,12 @@ fn all() -> Result<()> {
     run(&[FOX, SPIDERS, BUSTLE], "tests/expected/all.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn all_no_fast_path() -> Result<()> {
+    run(&["--no-fast-path", FOX, SPIDERS, BUSTLE], "tests/expected/all.out")
+}
+
 // --------------------------------------------------
 #[test]
 fn all_n() -> Result<()> {
@@ -201,3 +292,949 @@ fn all_n() -> Result<()> {
 fn all_b() -> Result<()> {
     run(&[FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn all_n_per_file() -> Result<()> {
+    run(
+        &["--number-per-file", "-n", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.n.per-file.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_b_per_file() -> Result<()> {
+    run(
+        &["--number-per-file", "-b", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.b.per-file.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_number_width_separator() -> Result<()> {
+    run(
+        &["-n", "--number-width", "3", "--number-separator", ": ", SPIDERS],
+        "tests/expected/spiders.txt.n.w3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1() -> Result<()> {
+    run_bytes(&[LATIN1], "tests/expected/latin1.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_n() -> Result<()> {
+    run_bytes(&["-n", LATIN1], "tests/expected/latin1.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b() -> Result<()> {
+    run_bytes(&["-b", LATIN1], "tests/expected/latin1.txt.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_stdin() -> Result<()> {
+    let input = fs::read(LATIN1)?;
+    let expected = fs::read("tests/expected/latin1.txt.n.out")?;
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(["-n"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_all() -> Result<()> {
+    run_bytes(&["--show-all", CONTROL], "tests/expected/control.txt.A.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_nonprinting() -> Result<()> {
+    run_bytes(&["--show-nonprinting", CONTROL], "tests/expected/control.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_ends() -> Result<()> {
+    run_bytes(&["--show-ends", CONTROL], "tests/expected/control.txt.E.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_tabs() -> Result<()> {
+    run_bytes(&["--show-tabs", CONTROL], "tests/expected/control.txt.T.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_e() -> Result<()> {
+    run_bytes(&["-e", CONTROL], "tests/expected/control.txt.e.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_t() -> Result<()> {
+    run_bytes(&["-t", CONTROL], "tests/expected/control.txt.t.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_n_show_all() -> Result<()> {
+    run_bytes(&["-nA", CONTROL], "tests/expected/control.txt.nA.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_b_show_all() -> Result<()> {
+    run_bytes(&["-bA", CONTROL], "tests/expected/control.txt.bA.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_s() -> Result<()> {
+    run(&["-s", BLANKS], "tests/expected/blanks.txt.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_sn() -> Result<()> {
+    run(&["--squeeze-blank", "-n", BLANKS], "tests/expected/blanks.txt.sn.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_sb() -> Result<()> {
+    run(&["-s", "-b", BLANKS], "tests/expected/blanks.txt.sb.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_twice_s() -> Result<()> {
+    run(&["-s", BLANKS, BLANKS], "tests/expected/blanks.txt.twice.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed_without_z() -> Result<()> {
+    run_bytes(&[FOX_GZ], FOX_GZ)
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_gz_z() -> Result<()> {
+    run(&["-z", FOX_GZ], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_zst_z() -> Result<()> {
+    run(&["--decompress", SPIDERS_ZST], "tests/expected/spiders.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_bz2_z_n() -> Result<()> {
+    run(&["-z", "-n", BUSTLE_BZ2], "tests/expected/the-bustle.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multi_gz_z() -> Result<()> {
+    run(&["-z", MULTI_GZ], "tests/expected/multi.txt.gz.z.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_mixed_z() -> Result<()> {
+    run(&["-z", FOX_GZ, SPIDERS_XZ, BUSTLE], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_mixed_z_n() -> Result<()> {
+    run(&["-zn", FOX, SPIDERS_XZ, BUSTLE_BZ2], "tests/expected/all.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_gz_z() -> Result<()> {
+    let input = fs::read(FOX_GZ)?;
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .arg("-z")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_appends_truncation_and_rotation() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "one\n")?;
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-n", "-f"])
+        .arg(&log)
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if tx.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let next_line = || rx.recv_timeout(Duration::from_secs(5));
+
+    assert_eq!(next_line()?, "     1\tone");
+
+    fs::OpenOptions::new().append(true).open(&log)?.write_all(b"two\n")?;
+    assert_eq!(next_line()?, "     2\ttwo");
+
+    fs::write(&log, "x\n")?;
+    assert_eq!(next_line()?, "     3\tx");
+
+    fs::rename(&log, dir.path().join("app.log.1"))?;
+    fs::write(&log, "rotated\n")?;
+    assert_eq!(next_line()?, "     4\trotated");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_follow_decompressed() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-z", FOX_GZ])
+        .timeout(std::time::Duration::from_secs(5))
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(predicate::str::contains("cannot follow a decompressed or decoded input"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_follow_decoded() -> Result<()> {
+    for flag in ["--to-unix", "--detect-bom", "--from-encoding=latin1", "--to-encoding=utf-16le"] {
+        Command::cargo_bin(PRG)?
+            .args(["-f", flag, FOX])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_n_lines() -> Result<()> {
+    run(
+        &["-n", "--lines", "2:3,-2:", BUSTLE],
+        "tests/expected/the-bustle.txt.n.lines.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_b_lines_stdin() -> Result<()> {
+    run_stdin(
+        BUSTLE,
+        &["-b", "--lines=-3:"],
+        "tests/expected/the-bustle.txt.b.lines.stdin.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_lines() -> Result<()> {
+    run(&["--lines", ":-7", BUSTLE, SPIDERS], "tests/expected/all.lines.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_n_lines() -> Result<()> {
+    run(
+        &["-n", "--lines", "1", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.n.lines.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_n_lines_global() -> Result<()> {
+    run(
+        &["-n", "--lines-global", "--lines", "9:11", BUSTLE, SPIDERS],
+        "tests/expected/all.n.lines-global.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_lines() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--lines", "0:2", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '0:2' for '--lines <RANGES>': line numbers start at 1",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_reverse() -> Result<()> {
+    run(&["--reverse", BUSTLE], "tests/expected/the-bustle.txt.reverse.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn reverse_squeeze_numbers() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", "-s", "-n"])
+        .write_stdin("a\n\n\n\nb\n")
+        .assert()
+        .success()
+        .stdout("     3\tb\n     2\t\n     1\ta\n");
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", "-s", "-b"])
+        .write_stdin("\n\na\n\n\nb\n\n")
+        .assert()
+        .success()
+        .stdout("\n     2\tb\n\n     1\ta\n\n");
+    // With another separator, a blank record is one that holds just the separator
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", "--separator=--", "-s", "-b"])
+        .write_stdin("a------b--")
+        .assert()
+        .success()
+        .stdout("     2\tb----     1\ta--");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_stdin_reverse() -> Result<()> {
+    run_stdin(BUSTLE, &["--reverse"], "tests/expected/the-bustle.txt.reverse.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_n_reverse() -> Result<()> {
+    run(
+        &["--reverse", "-n", BUSTLE, SPIDERS],
+        "tests/expected/all.n.reverse.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dashes_reverse_separator() -> Result<()> {
+    run(
+        &["--reverse", "--separator=--", DASHES],
+        "tests/expected/dashes.txt.reverse.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn reverse_regex_separator() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/digits.reverse.regex.out")?;
+    Command::cargo_bin(PRG)?
+        .write_stdin("a1b22c333")
+        .args(["--reverse", "--regex", "--separator", "[0-9]+"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_reverse() -> Result<()> {
+    let mut expected: Vec<Vec<u8>> = fs::read(LATIN1)?
+        .split_inclusive(|&b| b == b'\n')
+        .map(|line| line.to_vec())
+        .collect();
+    expected.reverse();
+    let output = Command::cargo_bin(PRG)?.args(["--reverse", LATIN1]).output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected.concat());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_eol_to_unix() -> Result<()> {
+    run(&["--to-unix", MIXED_EOL], "tests/expected/mixed-eol.txt.to-unix.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_eol_to_dos() -> Result<()> {
+    run(&["--to-dos", MIXED_EOL], "tests/expected/mixed-eol.txt.to-dos.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_eol_to_mac() -> Result<()> {
+    run(&["--to-mac", MIXED_EOL], "tests/expected/mixed-eol.txt.to-mac.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_eol_n_to_dos() -> Result<()> {
+    run(&["-n", "--to-dos", MIXED_EOL], "tests/expected/mixed-eol.txt.n.to-dos.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn detect_eol() -> Result<()> {
+    run(&["--detect-eol", FOX, MIXED_EOL, EMPTY], "tests/expected/detect-eol.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_two_eol_modes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--to-unix", "--to-dos", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn utf16le_bom() -> Result<()> {
+    run(&["--detect-bom", UTF16LE], "tests/expected/utf16-bom.csv.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn utf16be_bom_n() -> Result<()> {
+    run(&["-n", "--detect-bom", UTF16BE], "tests/expected/utf16-bom.csv.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn utf16le_bom_with_to_encoding() -> Result<()> {
+    run(&["--to-encoding", "utf-8", UTF16LE], "tests/expected/utf16-bom.csv.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bom_bytes_pass_through() -> Result<()> {
+    run_bytes(&[BOM_BIN], BOM_BIN)?;
+    run_bytes(&["-n", UTF16LE], "tests/expected/utf16le-bom.csv.n.raw.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bom_bytes_pass_through_stdin() -> Result<()> {
+    let input = fs::read(BOM_BIN)?;
+    let output = Command::cargo_bin(PRG)?.write_stdin(input.clone()).output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, input);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn shift_jis() -> Result<()> {
+    run(
+        &["--from-encoding", "shift_jis", SHIFT_JIS],
+        "tests/expected/shift_jis.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_from_encoding() -> Result<()> {
+    run(
+        &["--from-encoding", "latin1", LATIN1],
+        "tests/expected/latin1.txt.from-latin1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_to_utf16le() -> Result<()> {
+    run_bytes(
+        &["--to-encoding", "utf-16le", FOX],
+        "tests/expected/fox.txt.utf16le.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_encoding() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "klingon", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown encoding 'klingon'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn control_hex() -> Result<()> {
+    run(&["--hex", CONTROL], "tests/expected/control.txt.hex.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_hex_columns_group() -> Result<()> {
+    run(
+        &["--hex", "--hex-columns", "8", "--hex-group", "3", LATIN1],
+        "tests/expected/latin1.txt.hex.c8.g3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn control_unhex() -> Result<()> {
+    run_bytes(&["--unhex", "tests/expected/control.txt.hex.out"], CONTROL)
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_unhex_columns_group() -> Result<()> {
+    run_bytes(&["--unhex", "tests/expected/latin1.txt.hex.c8.g3.out"], LATIN1)
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_unhex() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--unhex", "tests/inputs/bad-dump.txt"])
+        .assert()
+        .failure()
+        .stdout("hi\n")
+        .stderr(predicate::str::contains("line 2: invalid hex digit"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn hello_highlight() -> Result<()> {
+    run(
+        &["--highlight", "--color", "always", "tests/inputs/hello.rs"],
+        "tests/expected/hello.rs.highlight.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn shebang_highlight_n() -> Result<()> {
+    run(
+        &[
+            "-n",
+            "--highlight",
+            "--color",
+            "always",
+            "tests/inputs/greet",
+        ],
+        "tests/expected/greet.n.highlight.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_language_override() -> Result<()> {
+    run(
+        &[
+            "--highlight",
+            "--color",
+            "always",
+            "--language",
+            "shell",
+            "tests/inputs/hello.rs",
+        ],
+        "tests/expected/hello.rs.highlight.shell.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_plain_when_piped() -> Result<()> {
+    let expected = fs::read_to_string("tests/inputs/hello.rs")?;
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", "tests/inputs/hello.rs"])
+        .assert()
+        .success()
+        .stdout(expected.clone());
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", "--color", "never", "tests/inputs/hello.rs"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_plain_into_output_file() -> Result<()> {
+    // script(1) runs catr with a terminal on stdout, where --color auto would colour
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.rs");
+    let command = format!(
+        "{} --highlight -o {} tests/inputs/hello.rs",
+        assert_cmd::cargo::cargo_bin(PRG).display(),
+        path.display()
+    );
+    let Ok(status) = std::process::Command::new("script")
+        .args(["-qec", &command, "/dev/null"])
+        .stdout(std::process::Stdio::null())
+        .status()
+    else {
+        eprintln!("script(1) is not available, skipping");
+        return Ok(());
+    };
+    assert!(status.success());
+    assert_eq!(
+        fs::read_to_string(&path)?,
+        fs::read_to_string("tests/inputs/hello.rs")?
+    );
+
+    // Asking for colours still writes them
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", "--color", "always", "-o", path.to_str().unwrap()])
+        .arg("tests/inputs/hello.rs")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&path)?,
+        fs::read_to_string("tests/expected/hello.rs.highlight.out")?
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn highlight_unknown_language_plain() -> Result<()> {
+    run(
+        &["--highlight", "--color", "always", FOX],
+        "tests/expected/fox.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_language() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", "--language", "cobol", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'cobol'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_streams_when_piped() -> Result<()> {
+    for paging in ["auto", "always", "never"] {
+        run(
+            &["--paging", paging, "-n", BUSTLE],
+            "tests/expected/the-bustle.txt.n.out",
+        )?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_paging() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--paging", "sometimes", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'sometimes'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn tar_member() -> Result<()> {
+    run(
+        &["tests/inputs/bundle.tar.gz:docs/spiders.txt"],
+        "tests/expected/spiders.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn zip_member_n() -> Result<()> {
+    run(
+        &["-n", "tests/inputs/bundle.zip:docs/spiders.txt"],
+        "tests/expected/spiders.txt.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn archive_members_numbered_across() -> Result<()> {
+    let expected = format!(
+        "{}{}",
+        fs::read_to_string("tests/expected/fox.txt.n.out")?,
+        "     2\tDon't worry, spiders,\n     3\tI keep house\n     4\tcasually.\n"
+    );
+    Command::cargo_bin(PRG)?
+        .args([
+            "-n",
+            "tests/inputs/bundle.zip:fox.txt",
+            "tests/inputs/bundle.tar.gz:./docs/spiders.txt",
+        ])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn list_archive() -> Result<()> {
+    run(
+        &["--list-archive", BUNDLE_TGZ],
+        "tests/expected/bundle.tar.gz.list.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn list_archives() -> Result<()> {
+    run(
+        &["--list-archive", BUNDLE_ZIP, BUNDLE_TGZ],
+        "tests/expected/bundles.list.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_missing_member() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/bundle.zip:nope.txt", FOX])
+        .assert()
+        .failure()
+        .stdout(fs::read_to_string("tests/expected/fox.txt.out")?)
+        .stderr(predicate::str::contains(
+            "tests/inputs/bundle.zip:nope.txt: no such member in the archive",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_list_not_archive() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--list-archive", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a tar or zip archive"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jsonl_numbers_across_files() -> Result<()> {
+    run(
+        &["--format", "jsonl", FOX, SPIDERS],
+        "tests/expected/fox-spiders.jsonl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jsonl_b_leaves_blank_lines_null() -> Result<()> {
+    run(
+        &["--format", "jsonl", "-b", BUSTLE],
+        "tests/expected/the-bustle.txt.b.jsonl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jsonl_invalid_utf8() -> Result<()> {
+    run(
+        &["--format", "jsonl", LATIN1],
+        "tests/expected/latin1.txt.jsonl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_jsonl_with_show_all() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "jsonl", "-A", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_directory() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([TREE, FOX])
+        .assert()
+        .failure()
+        .stdout(fs::read_to_string("tests/expected/fox.txt.out")?)
+        .stderr(predicate::str::contains(
+            "tests/inputs/tree: Is a directory",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_header() -> Result<()> {
+    run(
+        &["-r", "--header", TREE],
+        "tests/expected/tree.r.header.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_include_exclude_n() -> Result<()> {
+    run(
+        &["-rn", "--include", "*.conf", "--exclude", "old", TREE],
+        "tests/expected/tree.rn.include.exclude.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_include_path() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-r", "--include", "sub/*.conf", TREE])
+        .assert()
+        .success()
+        .stdout("name = \"c\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn header_without_recursive() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--header", FOX, "-"])
+        .write_stdin("hi\n")
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {} <==\n{}==> - <==\nhi\n",
+            FOX,
+            fs::read_to_string("tests/expected/fox.txt.out")?
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_include_without_recursive() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--include", "*.conf", TREE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--recursive"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn output_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.txt");
+    fs::write(&path, "old contents\n")?;
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-o", path.to_str().unwrap(), SPIDERS])
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(
+        fs::read_to_string(&path)?,
+        fs::read_to_string("tests/expected/spiders.txt.n.out")?
+    );
+    // The temporary file was renamed, not left behind
+    assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_output_is_input() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("a.txt");
+    fs::write(&path, "a\n")?;
+    let path = path.to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args([FOX, path, "-o", path])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "{}: input file is output file",
+            path
+        )));
+    assert_eq!(fs::read_to_string(path)?, "a\n");
+    assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_input_appended_to() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("a.txt");
+    fs::write(&path, "a\n")?;
+    let append = fs::OpenOptions::new().append(true).open(&path)?;
+    let output = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args([path.to_str().unwrap(), FOX])
+        .stdout(append)
+        .output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)?.contains("input file is output file"));
+    assert_eq!(
+        fs::read_to_string(&path)?,
+        format!("a\n{}", fs::read_to_string(FOX)?)
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bom_hex() -> Result<()> {
+    run(&["--hex", BOM_BIN], "tests/expected/bom.bin.hex.out")?;
+    run(&["--hex", "--to-encoding", "utf-8", BOM_BIN], "tests/expected/bom.bin.hex.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bom_unhex() -> Result<()> {
+    run_bytes(&["--unhex", "tests/expected/bom.bin.hex.out"], BOM_BIN)
+}
@@ -0,0 +1,5 @@
+// Greets the world
+fn main() {
+    let n = 42;
+    println!("hello {}", n);
+}
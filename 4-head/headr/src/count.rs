@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CountError {
+    #[error("expected a number, optionally followed by a suffix such as K, MB or GiB")]
+    NotANumber,
+    #[error("invalid suffix '{0}'")]
+    BadSuffix(String),
+    #[error("value too large")]
+    TooLarge,
+}
+
+/// How much of each file to print, in lines or bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Count {
+    /// The first N
+    First(u64),
+    /// Everything except the last N, written `-N`
+    AllBut(u64),
+}
+
+impl FromStr for Count {
+    type Err = CountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('-') {
+            Some(n) => Ok(Count::AllBut(parse_size(n)?)),
+            None => Ok(Count::First(parse_size(s.strip_prefix('+').unwrap_or(s))?)),
+        }
+    }
+}
+
+/// Parse a number with an optional GNU size suffix: `b` is 512, `K`, `M`, `G`
+/// and so on up to `Q` are powers of 1024, also written `KiB`, `MiB`, ...,
+/// and `KB`, `MB`, ... are powers of 1000. `k` and `m` may be lowercase.
+fn parse_size(s: &str) -> Result<u64, CountError> {
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(digits);
+    if number.is_empty() {
+        return Err(CountError::NotANumber);
+    }
+    let number: u64 = number.parse().map_err(|_| CountError::TooLarge)?;
+    number
+        .checked_mul(multiplier(suffix)?)
+        .ok_or(CountError::TooLarge)
+}
+
+fn multiplier(suffix: &str) -> Result<u64, CountError> {
+    let bad_suffix = || CountError::BadSuffix(suffix.to_string());
+    let mut chars = suffix.chars();
+    let power = match chars.next() {
+        None => return Ok(1),
+        Some('b') if chars.as_str().is_empty() => return Ok(512),
+        Some('k' | 'K') => 1,
+        Some('m' | 'M') => 2,
+        Some('G') => 3,
+        Some('T') => 4,
+        Some('P') => 5,
+        Some('E') => 6,
+        Some('Z') => 7,
+        Some('Y') => 8,
+        Some('R') => 9,
+        Some('Q') => 10,
+        Some(_) => return Err(bad_suffix()),
+    };
+    let base: u64 = match chars.as_str() {
+        "" | "iB" => 1024,
+        "B" => 1000,
+        _ => return Err(bad_suffix()),
+    };
+    base.checked_pow(power).ok_or(CountError::TooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Count, CountError};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("10".parse(), Ok(Count::First(10)));
+        assert_eq!("+3".parse(), Ok(Count::First(3)));
+        assert_eq!("-3".parse(), Ok(Count::AllBut(3)));
+        assert_eq!("-0".parse(), Ok(Count::AllBut(0)));
+        assert_eq!("--3".parse::<Count>(), Err(CountError::NotANumber));
+        assert_eq!("x".parse::<Count>(), Err(CountError::NotANumber));
+        assert_eq!("".parse::<Count>(), Err(CountError::NotANumber));
+    }
+
+    #[test]
+    fn test_suffixes() {
+        let first = |s: &str| s.parse::<Count>();
+        assert_eq!(first("2b"), Ok(Count::First(1024)));
+        assert_eq!(first("1K"), Ok(Count::First(1024)));
+        assert_eq!(first("1k"), Ok(Count::First(1024)));
+        assert_eq!(first("1KiB"), Ok(Count::First(1024)));
+        assert_eq!(first("1KB"), Ok(Count::First(1000)));
+        assert_eq!(first("3M"), Ok(Count::First(3 << 20)));
+        assert_eq!(first("3MB"), Ok(Count::First(3_000_000)));
+        assert_eq!(first("-1MiB"), Ok(Count::AllBut(1 << 20)));
+        assert_eq!(first("5G"), Ok(Count::First(5 << 30)));
+        assert_eq!(first("15E"), Ok(Count::First(15 << 60)));
+        assert_eq!(first("18446744073709551615"), Ok(Count::First(u64::MAX)));
+    }
+
+    #[test]
+    fn test_bad_suffixes() {
+        let err = |s: &str| s.parse::<Count>().unwrap_err();
+        assert_eq!(err("5X"), CountError::BadSuffix("X".to_string()));
+        assert_eq!(err("5g"), CountError::BadSuffix("g".to_string()));
+        assert_eq!(err("5bB"), CountError::BadSuffix("bB".to_string()));
+        assert_eq!(err("5KiBs"), CountError::BadSuffix("KiBs".to_string()));
+        assert_eq!(err("5 K"), CountError::BadSuffix(" K".to_string()));
+        assert_eq!(err("16E"), CountError::TooLarge);
+        assert_eq!(err("1Z"), CountError::TooLarge);
+        assert_eq!(err("18446744073709551616"), CountError::TooLarge);
+    }
+}
@@ -1,6 +1,14 @@
-use clap::{Parser, value_parser};
+use clap::Parser;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+};
 use thiserror::Error;
-use std::{error::Error, fs::File, io::{BufRead, BufReader}};
+
+mod count;
+pub use count::Count;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -12,8 +20,25 @@ pub enum BadFileError {
         #[source]
         from: Box<dyn Error>,
     },
+    #[error("{filename}: {from}")]
+    Read {
+        filename: String,
+        #[source]
+        from: Box<dyn Error>,
+    },
 }
 
+/// Returned by `run` after all files were processed if some of them failed.
+/// Each of them was already reported on stderr as it came up
+#[derive(Debug, Error)]
+#[error("{} file(s) could not be read", .0.len())]
+pub struct FailedFiles(pub Vec<BadFileError>);
+
+/// Stdout could not be written to, e.g. because the reader closed the pipe;
+/// `run` stops at once instead of going on to the next file
+#[derive(Debug, Error)]
+#[error("write error: {0}")]
+pub struct WriteError(#[from] io::Error);
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -22,13 +47,47 @@ pub struct Config {
     #[arg(default_values_t = ["-".to_string()])]
     files: Vec<String>,
 
-    /// Number of lines to print
-    #[arg(short='n', long, default_value_t = 10, conflicts_with = "bytes", value_parser=value_parser!(u32).range(1..))]
-    lines : u32,
+    /// Number of lines to print; with a leading '-', print all but the last that many.
+    /// Takes a size suffix such as K, MB or GiB
+    #[arg(
+        short = 'n',
+        long,
+        default_value = "10",
+        conflicts_with = "bytes",
+        allow_hyphen_values = true,
+        value_parser = parse_count
+    )]
+    lines: Count,
+
+    /// Number of bytes to print; with a leading '-', print all but the last that many.
+    /// Takes a size suffix: b (512), K, M, G, ... (powers of 1024, also KiB, MiB, ...)
+    /// or KB, MB, GB, ... (powers of 1000)
+    #[arg(short = 'c', long, allow_hyphen_values = true, value_parser = parse_count)]
+    bytes: Option<Count>,
+
+    /// Never print headers giving file names
+    #[arg(short, long, visible_alias = "silent", overrides_with = "verbose")]
+    quiet: bool,
+
+    /// Always print headers giving file names, even for a single file
+    #[arg(short, long, overrides_with = "quiet")]
+    verbose: bool,
+
+    /// Template for the headers, where {} stands for the file name
+    #[arg(long, value_name = "TEMPLATE", default_value = "==> {} <==")]
+    header_format: String,
+}
 
-    /// Number of bytes to print
-    #[arg(short = 'c', long, value_parser=value_parser!(u32).range(1..))]
-    bytes: Option<u32>,
+/// Parse the value of `-n` or `-c`. Hyphen values are allowed for counts such
+/// as `-1K`, but anything else starting with '-' is taken for a misplaced option,
+/// so `-n -v` fails instead of reading `-v` as the count
+fn parse_count(s: &str) -> Result<Count, String> {
+    match s.strip_prefix('-') {
+        Some(rest) if !rest.starts_with(|c: char| c.is_ascii_digit()) => {
+            Err(format!("a count is required, found the option '{s}'"))
+        }
+        _ => s.parse().map_err(|e: count::CountError| e.to_string()),
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -36,39 +95,129 @@ pub fn get_args() -> MyResult<Config> {
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    let has_multiple_files = config.files.len() > 1;
-
-    let results = config.files.iter().enumerate().map(|(i, filename)| -> MyResult<()> {
-        if i > 0 {
-            println!();
+    let headers = !config.quiet && (config.verbose || config.files.len() > 1);
+    let mut failed = Vec::new();
+    let mut first = true;
+    let mut out = io::stdout().lock();
+
+    for filename in &config.files {
+        let file = match open(filename) {
+            Ok(file) => file,
+            Err(from) => {
+                report(
+                    &mut failed,
+                    BadFileError::Open {
+                        filename: filename.clone(),
+                        from,
+                    },
+                );
+                continue;
+            }
+        };
+
+        if headers {
+            if !first {
+                writeln!(out).map_err(WriteError)?;
+            }
+            writeln!(out, "{}", config.header_format.replace("{}", filename))
+                .map_err(WriteError)?;
         }
-
-        if has_multiple_files {
-            println!("==> {} <==", filename);
+        first = false;
+
+        match head(&config, file, &mut out) {
+            Ok(()) => {}
+            Err(err) if err.is::<WriteError>() => return Err(err),
+            Err(from) => report(
+                &mut failed,
+                BadFileError::Read {
+                    filename: filename.clone(),
+                    from,
+                },
+            ),
         }
+    }
 
-        let mut file = open(filename).map_err(|e| BadFileError::Open { filename: filename.clone(), from: e })?;
-        if let Some(bytes) = config.bytes {
-            let mut buf : Vec<u8> = vec![0; bytes as usize];
-            let length = file.read(&mut buf)?;
-            print!("{}", String::from_utf8_lossy(&buf[..length]));
+    out.flush().map_err(WriteError)?;
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Box::new(FailedFiles(failed)))
+    }
+}
 
-        } else {
-            LinesWithNewLine::from(file).take(config.lines as usize).try_for_each(|line| -> MyResult<()> {
-                print!("{}", line?);
-                Ok(())
-            })?;
+/// Print the part of `file` selected by `-n` or `-c`. Errors writing to `out`
+/// come back as [`WriteError`], to tell them from errors reading `file`
+fn head(config: &Config, file: Box<dyn BufRead>, out: &mut impl Write) -> MyResult<()> {
+    match (config.bytes, config.lines) {
+        (Some(Count::First(bytes)), _) => {
+            // Keep reading until the count or EOF: pipes hand over data in pieces,
+            // and huge counts must not size a buffer. The bytes go out as they are,
+            // even when they are not UTF-8
+            let mut file = file.take(bytes);
+            loop {
+                let chunk = file.fill_buf()?;
+                if chunk.is_empty() {
+                    break;
+                }
+                out.write_all(chunk).map_err(WriteError)?;
+                let len = chunk.len();
+                file.consume(len);
+            }
         }
+        (Some(Count::AllBut(bytes)), _) => all_but_bytes(file, bytes, out)?,
+        (None, Count::First(lines)) => LinesWithNewLine::from(file)
+            .take(usize::try_from(lines).unwrap_or(usize::MAX))
+            .try_for_each(|line| -> MyResult<()> {
+                out.write_all(line?.as_bytes()).map_err(WriteError)?;
+                Ok(())
+            })?,
+        (None, Count::AllBut(lines)) => all_but_lines(file, lines, out)?,
+    }
+    Ok(())
+}
 
+fn report(failed: &mut Vec<BadFileError>, err: BadFileError) {
+    eprintln!("headr: {}", err);
+    failed.push(err);
+}
 
+/// Print all but the last `n` lines, holding back no more than those `n`
+fn all_but_lines(file: Box<dyn BufRead>, n: u64, out: &mut impl Write) -> MyResult<()> {
+    let mut held = VecDeque::new();
+    for line in LinesWithNewLine::from(file) {
+        held.push_back(line?);
+        if held.len() as u64 > n {
+            if let Some(line) = held.pop_front() {
+                out.write_all(line.as_bytes()).map_err(WriteError)?;
+            }
+        }
+    }
+    Ok(())
+}
 
-        Ok(())
-    });
-
-    match results.collect::<MyResult<()>>() {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e),
+/// Print all but the last `n` bytes, holding back no more than those `n` and one buffer
+fn all_but_bytes(mut file: Box<dyn BufRead>, n: u64, out: &mut impl Write) -> MyResult<()> {
+    let mut held = VecDeque::new();
+    loop {
+        let chunk = file.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        held.extend(chunk);
+        let len = chunk.len();
+        file.consume(len);
+
+        if held.len() as u64 > n {
+            let ready = held.len() - n as usize;
+            let (front, back) = held.as_slices();
+            let from_front = ready.min(front.len());
+            out.write_all(&front[..from_front]).map_err(WriteError)?;
+            out.write_all(&back[..ready - from_front])
+                .map_err(WriteError)?;
+            held.drain(..ready);
+        }
     }
+    Ok(())
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -78,8 +227,7 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-
-struct LinesWithNewLine (Box<dyn BufRead>);
+struct LinesWithNewLine(Box<dyn BufRead>);
 
 impl From<Box<dyn BufRead>> for LinesWithNewLine {
     fn from(buf_read: Box<dyn BufRead>) -> Self {
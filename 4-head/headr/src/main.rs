@@ -1,6 +1,9 @@
 fn main() {
     if let Err(e) = headr::get_args().and_then(headr::run) {
-        eprintln!("Error: {}", e);
+        // Files that could not be read were already reported one by one
+        if !e.is::<headr::FailedFiles>() {
+            eprintln!("Error: {}", e);
+        }
         std::process::exit(1);
     }
 }
@@ -5,6 +5,9 @@ use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs::{self, File};
 use std::io::prelude::*;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
 
 const PRG: &str = "headr";
 const EMPTY: &str = "./tests/inputs/empty.txt";
@@ -12,6 +15,7 @@ const ONE: &str = "./tests/inputs/one.txt";
 const TWO: &str = "./tests/inputs/two.txt";
 const THREE: &str = "./tests/inputs/three.txt";
 const TWELVE: &str = "./tests/inputs/twelve.txt";
+const BINARY: &str = "./tests/inputs/binary.bin";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -35,10 +39,10 @@ fn gen_bad_file() -> String {
 // --------------------------------------------------
 #[test]
 fn dies_bad_bytes() -> Result<()> {
-    let bad = random_string();
+    let bad = format!("x{}", random_string());
     let expected = format!(
         "invalid value '{bad}' for \
-        '--bytes <BYTES>': invalid digit found in string"
+        '--bytes <BYTES>': expected a number, optionally followed by a suffix such as K, MB or GiB"
     );
 
     Command::cargo_bin(PRG)?
@@ -50,13 +54,35 @@ fn dies_bad_bytes() -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bad_bytes_suffix() -> Result<()> {
+    for (bad, reason) in [
+        ("5X", "invalid suffix 'X'"),
+        ("5g", "invalid suffix 'g'"),
+        ("5KiBs", "invalid suffix 'KiBs'"),
+        ("16E", "value too large"),
+        ("-1Z", "value too large"),
+        ("18446744073709551616", "value too large"),
+    ] {
+        let expected = format!("invalid value '{bad}' for '--bytes <BYTES>': {reason}");
+        Command::cargo_bin(PRG)?
+            .args(["-c", bad, EMPTY])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn dies_bad_lines() -> Result<()> {
-    let bad = random_string();
+    let bad = format!("x{}", random_string());
     let expected = format!(
         "error: invalid value '{bad}' for \
-        '--lines <LINES>': invalid digit found in string"
+        '--lines <LINES>': expected a number, optionally followed by a suffix such as K, MB or GiB"
     );
     Command::cargo_bin(PRG)?
         .args(["-n", &bad, EMPTY])
@@ -67,6 +93,25 @@ fn dies_bad_lines() -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bad_lines_suffix() -> Result<()> {
+    for (bad, reason) in [
+        ("2Kb", "invalid suffix 'Kb'"),
+        ("-3 ", "invalid suffix ' '"),
+        ("100000000000000000000", "value too large"),
+    ] {
+        let expected = format!("invalid value '{bad}' for '--lines <LINES>': {reason}");
+        Command::cargo_bin(PRG)?
+            .args(["-n", bad, EMPTY])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn dies_bytes_and_lines() -> Result<()> {
@@ -95,6 +140,71 @@ fn skips_bad_file() -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn continues_after_bad_files() -> Result<()> {
+    let bad1 = gen_bad_file();
+    let bad2 = format!("{bad1}-2");
+    let expected = fs::read("tests/expected/one.bad.two.out")?;
+    let output = Command::cargo_bin(PRG)?
+        .args([&bad1, ONE, &bad2, TWO])
+        .output()
+        .expect("fail");
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        format!(
+            "headr: {bad1}: No such file or directory (os error 2)\n\
+             headr: {bad2}: No such file or directory (os error 2)\n"
+        )
+    );
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn reports_directory() -> Result<()> {
+    let expected = fs::read("tests/expected/one.txt.out")?;
+    let output = Command::cargo_bin(PRG)?
+        .args(["-n", "1", "./tests", ONE])
+        .output()
+        .expect("fail");
+    assert!(!output.status.success());
+    assert!(output.stdout.ends_with(&expected));
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("./tests: Is a directory"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stops_on_write_error() -> Result<()> {
+    let bad = gen_bad_file();
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-c", "1M", "-", &bad])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Nobody reads the output, so writing it fails once the pipe buffer is full
+    drop(child.stdout.take());
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let producer = thread::spawn(move || stdin.write_all(&[b'x'; 1 << 20]));
+
+    let output = child.wait_with_output()?;
+    let _ = producer.join().expect("producer panicked");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.starts_with("Error: write error: "), "{stderr}");
+    assert!(!stderr.contains(&bad), "{stderr}");
+
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> Result<()> {
     // Extra work here due to lossy UTF
@@ -134,6 +244,32 @@ fn run_stdin(
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_bytes(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin_bytes(args: &[&str], input_file: &str, expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let input = fs::read(input_file)?;
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(args)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -353,6 +489,41 @@ fn twelve_c4() -> Result<()> {
     run(&[TWELVE, "-c", "4"], "tests/expected/twelve.txt.c4.out")
 }
 
+#[test]
+fn twelve_c1k() -> Result<()> {
+    run(&[TWELVE, "-c", "1K"], TWELVE)
+}
+
+#[test]
+fn twelve_c1b() -> Result<()> {
+    run(&[TWELVE, "-c", "1b"], TWELVE)
+}
+
+#[test]
+fn twelve_c_huge() -> Result<()> {
+    run(&[TWELVE, "-c", "15E"], TWELVE)
+}
+
+#[test]
+fn twelve_n1kb() -> Result<()> {
+    run(&[TWELVE, "-n", "1KB"], TWELVE)
+}
+
+#[test]
+fn twelve_n_max() -> Result<()> {
+    run(&[TWELVE, "-n", "18446744073709551615"], TWELVE)
+}
+
+#[test]
+fn twelve_c_minus_1k() -> Result<()> {
+    run(&[TWELVE, "-c", "-1KiB"], EMPTY)
+}
+
+#[test]
+fn twelve_n_minus_1m() -> Result<()> {
+    run(&[TWELVE, "-n", "-1M"], EMPTY)
+}
+
 #[test]
 fn twelve_stdin() -> Result<()> {
     run_stdin(&[], TWELVE, "tests/expected/twelve.txt.out")
@@ -423,3 +594,296 @@ fn multiple_files_c4() -> Result<()> {
         "tests/expected/all.c4.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn empty_n_minus2() -> Result<()> {
+    run(&[EMPTY, "-n", "-2"], "tests/expected/empty.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_c_minus4() -> Result<()> {
+    run(&[EMPTY, "-c", "-4"], "tests/expected/empty.txt.c-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_n_minus1_exact() -> Result<()> {
+    run(&[ONE, "-n", "-1"], "tests/expected/one.txt.n-1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_n_minus2_short() -> Result<()> {
+    run(&[ONE, "-n", "-2"], "tests/expected/one.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c_minus4() -> Result<()> {
+    run(&[ONE, "-c", "-4"], "tests/expected/one.txt.c-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c_minus23_exact() -> Result<()> {
+    run(&[ONE, "-c", "-23"], "tests/expected/one.txt.c-23.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c_minus30_short() -> Result<()> {
+    run(&[ONE, "-c", "-30"], "tests/expected/one.txt.c-30.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn two_n_minus2_exact() -> Result<()> {
+    run(&[TWO, "-n", "-2"], "tests/expected/two.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn two_c_minus4() -> Result<()> {
+    run(&[TWO, "-c", "-4"], "tests/expected/two.txt.c-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn three_n_minus2() -> Result<()> {
+    run(&[THREE, "-n", "-2"], "tests/expected/three.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn three_c_minus4() -> Result<()> {
+    run(&[THREE, "-c", "-4"], "tests/expected/three.txt.c-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_n_minus2() -> Result<()> {
+    run(&[TWELVE, "-n", "-2"], "tests/expected/twelve.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_c_minus4() -> Result<()> {
+    run(&[TWELVE, "-c", "-4"], "tests/expected/twelve.txt.c-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_n_minus2_stdin() -> Result<()> {
+    run_stdin(&["-n", "-2"], TWELVE, "tests/expected/twelve.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_c_minus4_stdin() -> Result<()> {
+    run_stdin(&["-c", "-4"], TWELVE, "tests/expected/twelve.txt.c-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_n_minus2() -> Result<()> {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TWELVE, "-n", "-2"],
+        "tests/expected/all.n-2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_c_minus4() -> Result<()> {
+    run(
+        &["-c", "-4", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.c-4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c1() -> Result<()> {
+    run_bytes(&[BINARY, "-c", "1"], "tests/expected/binary.bin.c1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c100() -> Result<()> {
+    run_bytes(&[BINARY, "-c", "100"], "tests/expected/binary.bin.c100.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c260_splits_multibyte() -> Result<()> {
+    run_bytes(&[BINARY, "-c", "260"], "tests/expected/binary.bin.c260.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c_minus4() -> Result<()> {
+    run_bytes(&[BINARY, "-c", "-4"], "tests/expected/binary.bin.c-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c_minus7() -> Result<()> {
+    run_bytes(&[BINARY, "-c", "-7"], "tests/expected/binary.bin.c-7.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c260_stdin() -> Result<()> {
+    run_stdin_bytes(&["-c", "260"], BINARY, "tests/expected/binary.bin.c260.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c1_raw() -> Result<()> {
+    run_bytes(&[ONE, "-c", "1"], "tests/expected/one.txt.c1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn slow_pipe_c() -> Result<()> {
+    let input = fs::read(BINARY)?;
+    let expected = fs::read("tests/expected/binary.bin.c260.out")?;
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-c", "260"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let producer = thread::spawn(move || -> std::io::Result<()> {
+        for chunk in input.chunks(50) {
+            stdin.write_all(chunk)?;
+            stdin.flush()?;
+            thread::sleep(Duration::from_millis(30));
+        }
+        Ok(())
+    });
+
+    let output = child.wait_with_output()?;
+    // headr may exit before the producer is done, which then sees a broken pipe
+    let _ = producer.join().expect("producer panicked");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn one_verbose() -> Result<()> {
+    run(&["-v", ONE], "tests/expected/one.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_verbose_default_format() -> Result<()> {
+    run(
+        &["--verbose", "--header-format", "==> {} <==", ONE],
+        "tests/expected/one.txt.v.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_quiet() -> Result<()> {
+    run(&["-q", ONE, TWO, THREE], "tests/expected/all.q.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_silent() -> Result<()> {
+    run(&["--silent", ONE, TWO, THREE], "tests/expected/all.q.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_verbose_then_quiet() -> Result<()> {
+    run(
+        &["-n", "1", "-v", "-q", ONE, TWO],
+        "tests/expected/all.n1.vq.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_quiet_then_verbose() -> Result<()> {
+    run(
+        &["-n", "1", "-q", "-v", ONE, TWO],
+        "tests/expected/all.n1.qv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_header_format() -> Result<()> {
+    run(
+        &["-n", "1", "--header-format", "## {}", ONE, TWO],
+        "tests/expected/all.n1.markdown.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn one_n3_then_verbose() -> Result<()> {
+    run(&["-n", "3", "-v", ONE], "tests/expected/one.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_verbose_then_n3() -> Result<()> {
+    run(&["-v", "-n", "3", ONE], "tests/expected/one.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn two_verbose_then_n_minus1() -> Result<()> {
+    run(&["-v", "-n", "-1", TWO], "tests/expected/two.txt.n-1.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_quiet_then_c5() -> Result<()> {
+    run(
+        &["-q", "-c", "5", ONE, TWO, THREE],
+        "tests/expected/all.c5.q.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_c5_then_quiet() -> Result<()> {
+    run(
+        &["-c", "5", "-q", ONE, TWO, THREE],
+        "tests/expected/all.c5.q.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_option_as_count() -> Result<()> {
+    for (args, expected) in [
+        (["-n", "-v"], "invalid value '-v' for '--lines <LINES>'"),
+        (["-c", "-q"], "invalid value '-q' for '--bytes <BYTES>'"),
+        (
+            ["--lines", "--verbose"],
+            "invalid value '--verbose' for '--lines <LINES>'",
+        ),
+    ] {
+        let expected = format!("{expected}: a count is required, found the option");
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .arg(ONE)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+
+    Ok(())
+}